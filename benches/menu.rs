@@ -0,0 +1,108 @@
+//! Matching and scanning over a generated corpus, run with `cargo bench`.
+//! Criterion keeps the previous run's results in `target/criterion` and
+//! reports the change against them.
+
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use rmenu_ng::command::Command;
+use rmenu_ng::core::{FilterCache, filtered};
+use rmenu_ng::desktop_entry::parse_desktop_file;
+use rmenu_ng::matcher::{MatchMode, fuzzy_score};
+use rmenu_ng::scanner::scan_dirs;
+use std::fs;
+
+/// Corpus sizes, from a sparse system to one with every Flatpak installed
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+const WORDS: [&str; 16] = [
+    "Firefox", "Web", "Browser", "Text", "Editor", "Terminal", "Files", "Image", "Viewer", "Music",
+    "Player", "Settings", "Office", "Writer", "System", "Monitor",
+];
+
+/// A name of two or three words, the same for the same `i`
+fn name(i: usize) -> String {
+    let word = |n: usize| WORDS[n % WORDS.len()];
+    match i % 3 {
+        0 => format!("{} {}", word(i), word(i / 7)),
+        _ => format!("{} {} {}", word(i), word(i / 3), word(i / 11)),
+    }
+}
+
+fn desktop_file(i: usize) -> String {
+    format!(
+        "[Desktop Entry]\nType=Application\nName={name}\nName[de]={name} (de)\n\
+         GenericName={generic}\nComment=Does things with {generic}\n\
+         Exec=app-{i} %U\nIcon=app-{i}\nCategories=Utility;Development;\n\
+         Keywords=one;two;three;\nActions=new-window;\n\n\
+         [Desktop Action new-window]\nName=New Window\nExec=app-{i} --new-window\n",
+        name = name(i),
+        generic = name(i / 5),
+    )
+}
+
+fn corpus(n: usize) -> Vec<Command> {
+    (0..n)
+        .map(|i| Command::new(format!("app-{}.desktop", i), name(i), format!("app-{}", i)))
+        .collect()
+}
+
+fn matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filtered");
+    for n in SIZES {
+        let commands = corpus(n);
+        for (mode, query) in [(MatchMode::Fuzzy, "tedit"), (MatchMode::Substring, "edit")] {
+            let id = BenchmarkId::new(format!("{:?}", mode), n);
+            group.bench_with_input(id, &commands, |b, commands| {
+                b.iter(|| {
+                    // A new cache each time, or substring queries only re-check
+                    // the previous matches
+                    let candidates = commands.iter().map(Command::display);
+                    let mut cache = FilterCache::default();
+                    filtered(candidates, black_box(query), mode, 0, &[], &mut cache)
+                })
+            });
+        }
+    }
+    group.finish();
+
+    c.bench_function("fuzzy_score", |b| {
+        let names: Vec<String> = (0..100).map(name).collect();
+        b.iter(|| {
+            names
+                .iter()
+                .filter_map(|n| fuzzy_score(n, black_box("wbrs")))
+                .count()
+        })
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    let contents = desktop_file(1);
+    c.bench_function("parse_desktop_file", |b| {
+        b.iter(|| parse_desktop_file(black_box(&contents)).unwrap())
+    });
+
+    let mut group = c.benchmark_group("scan_dirs");
+    group.sample_size(20);
+    for n in SIZES {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..n {
+            fs::write(
+                dir.path().join(format!("app-{}.desktop", i)),
+                desktop_file(i),
+            )
+            .unwrap();
+        }
+        let dirs = [dir.path().to_path_buf()];
+        group.bench_with_input(BenchmarkId::from_parameter(n), &dirs, |b, dirs| {
+            b.iter(|| {
+                let scan = scan_dirs(dirs);
+                assert_eq!(scan.files.len(), n);
+                scan
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, matching, parsing);
+criterion_main!(benches);
@@ -0,0 +1,368 @@
+use crate::config::{Anchor, AppConfig, Layout};
+
+/// Printed for `--help`
+pub const HELP: &str = "\
+Usage: rmenu [OPTIONS]
+
+Lists the installed applications and starts the one picked.
+
+Options:
+      --stdin            List the lines read from stdin instead, like dmenu
+      --with-fields      With --stdin, read key, display text and command
+                         from each line, separated by tabs
+      --delimiter SEP    Separate --with-fields fields with SEP instead
+      --index            With --stdin, print the picked line's position
+      --print            Print the picked entry's command instead of running it
+      --dry-run          Show what the picked entry would run on stderr
+                         instead of running it
+      --clipboard        Copy the picked entry's text to the clipboard
+      --multi            Let several entries be marked and print them all
+      --password         Hide the input and print it on Enter
+      --shell            Run commands through $SHELL -c
+      --open FILE        List the applications that can open FILE
+      --profile NAME     Use colors.NAME.ron and app.NAME.ron where they
+                         exist instead of colors.ron and app.ron
+  -p, --prompt TEXT      Show TEXT left of the input
+  -l LINES               List up to LINES rows, in the vertical layout
+  -b                     Put the window at the bottom of the screen
+  -h, --help             Print this help and exit
+  -V, -v, --version      Print the version and exit
+
+For scripts written for dmenu, -p, -l, -b and -v work as they do there.
+dmenu's -i, -f, -m, -w, -fn, -nb, -nf, -sb and -sf are accepted and
+ignored: matching always ignores case, and fonts and colors come from
+colors.ron. Pass --stdin to list the lines piped in, as dmenu does.
+
+Exits with 1 if the menu is closed without picking anything.
+";
+
+/// dmenu flags taken for compatibility and ignored, with whether they take a
+/// value
+const IGNORED_DMENU_FLAGS: &[(&str, bool)] = &[
+    ("-i", false),
+    ("-f", false),
+    ("-m", true),
+    ("-w", true),
+    ("-fn", true),
+    ("-nb", true),
+    ("-nf", true),
+    ("-sb", true),
+    ("-sf", true),
+];
+
+/// Printed for `--version`
+pub const VERSION: &str = concat!("rmenu ", env!("CARGO_PKG_VERSION"));
+
+/// Options given on the command line
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Read the menu entries from stdin, one per line, like dmenu
+    pub stdin: bool,
+    /// Print the selected entry's command to stdout instead of running it
+    pub print: bool,
+    /// Print the argv the selected entry would be run with to stderr instead
+    /// of running it, terminal included, see `launcher::entry_argv`
+    pub dry_run: bool,
+    /// Copy the selected entry's text to the clipboard instead of running it.
+    /// The text is printed to stdout as well, see `RMenuApp::copy`.
+    pub clipboard: bool,
+    /// Hide the typed text and list nothing, printing the input on Enter
+    pub password: bool,
+    /// Let several entries be marked, printing all of them on Enter
+    pub multi: bool,
+    /// With `--stdin`, print the selected line's zero-based position in the
+    /// input instead of its text
+    pub index: bool,
+    /// With `--stdin`, split each line on `delimiter` into a key, display text
+    /// and command, see `Command::from_fields`
+    pub with_fields: bool,
+    /// What `--with-fields` splits lines on, a tab unless given
+    pub delimiter: Option<String>,
+    /// Run selected commands through the user's shell, sets `AppConfig::shell`
+    pub shell: bool,
+    /// List the applications that can open this file, and open it with the
+    /// chosen one
+    pub open: Option<String>,
+    /// Overrides `AppConfig::prompt`
+    pub prompt: Option<String>,
+    /// Picks the config files, see `config::config_paths_in`
+    pub profile: Option<String>,
+    /// dmenu's `-l`: overrides `AppConfig::lines` and picks the vertical
+    /// layout
+    pub lines: Option<usize>,
+    /// dmenu's `-b`: anchors the window to the bottom of the screen, see
+    /// `AppConfig::layer_shell`
+    pub bottom: bool,
+    /// Print [`HELP`] and exit
+    pub help: bool,
+    /// Print [`VERSION`] and exit
+    pub version: bool,
+}
+
+impl Args {
+    /// Parses the process arguments
+    pub fn parse() -> Result<Args, String> {
+        Args::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses `args`, which must not include the program name
+    pub fn parse_from<I, S>(args: I) -> Result<Args, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().map(Into::into);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--stdin" => parsed.stdin = true,
+                "--print" => parsed.print = true,
+                "--dry-run" => parsed.dry_run = true,
+                "--clipboard" => parsed.clipboard = true,
+                "--password" => parsed.password = true,
+                "--multi" => parsed.multi = true,
+                "--index" => parsed.index = true,
+                "--shell" => parsed.shell = true,
+                "--with-fields" => parsed.with_fields = true,
+                "--delimiter" => parsed.delimiter = Some(value(&arg, args.next())?),
+                "-p" | "--prompt" => parsed.prompt = Some(value(&arg, args.next())?),
+                "--open" => parsed.open = Some(value(&arg, args.next())?),
+                "--profile" => parsed.profile = Some(value(&arg, args.next())?),
+                "-l" => {
+                    let lines = value(&arg, args.next())?;
+                    let lines = lines
+                        .parse()
+                        .map_err(|_| format!("-l needs a number of lines, not {:?}", lines))?;
+                    parsed.lines = Some(lines);
+                }
+                "-b" => parsed.bottom = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "-v" | "--version" => parsed.version = true,
+                other => match IGNORED_DMENU_FLAGS.iter().find(|(flag, _)| *flag == other) {
+                    Some(&(_, true)) => {
+                        value(&arg, args.next())?;
+                    }
+                    Some(&(_, false)) => {}
+                    None => return Err(format!("unknown argument: {}", other)),
+                },
+            }
+        }
+        // Whatever else was given, these just print and exit
+        if parsed.help || parsed.version {
+            return Ok(parsed);
+        }
+        if parsed.index && !parsed.stdin {
+            return Err("--index requires --stdin".to_string());
+        }
+        if parsed.with_fields && !parsed.stdin {
+            return Err("--with-fields requires --stdin".to_string());
+        }
+        if parsed.with_fields && parsed.index {
+            return Err("--with-fields can't be combined with --index".to_string());
+        }
+        match parsed.delimiter.as_deref() {
+            Some("") => return Err("--delimiter can't be empty".to_string()),
+            Some(_) if !parsed.with_fields => {
+                return Err("--delimiter requires --with-fields".to_string());
+            }
+            _ => {}
+        }
+        if let Some(profile) = &parsed.profile
+            && (profile.is_empty() || profile.contains('/'))
+        {
+            return Err(format!("invalid profile name: {:?}", profile));
+        }
+        if parsed.open.is_some() && parsed.stdin {
+            return Err("--open can't be combined with --stdin".to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// Applies the options that override config file values, so they keep
+    /// winning when the config is reloaded.
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(prompt) = &self.prompt {
+            config.prompt = prompt.clone();
+        }
+        if self.shell {
+            config.shell = true;
+        }
+        if let Some(lines) = self.lines {
+            config.lines = lines;
+            config.layout = Layout::Vertical;
+        }
+        if self.bottom {
+            config.layer_shell.enabled = true;
+            config.layer_shell.anchor = Anchor::Bottom;
+        }
+    }
+}
+
+fn value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("{} requires a value", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flags() {
+        let args = Args::parse_from([
+            "--stdin",
+            "--print",
+            "--dry-run",
+            "--clipboard",
+            "--password",
+            "--multi",
+            "--index",
+            "--shell",
+        ])
+        .unwrap();
+        assert!(args.stdin);
+        assert!(args.print);
+        assert!(args.dry_run);
+        assert!(args.clipboard);
+        assert!(args.password);
+        assert!(args.multi);
+        assert!(args.index);
+        assert!(args.shell);
+
+        let args = Args::parse_from(Vec::<String>::new()).unwrap();
+        assert!(!args.stdin);
+        assert!(!args.print);
+        assert!(!args.dry_run);
+        assert!(!args.clipboard);
+        assert!(!args.password);
+        assert!(!args.multi);
+        assert!(!args.index);
+        assert!(!args.shell);
+    }
+
+    #[test]
+    fn parses_prompt() {
+        let args = Args::parse_from(["-p", "Run:"]).unwrap();
+        assert_eq!(args.prompt.as_deref(), Some("Run:"));
+        assert!(Args::parse_from(["--prompt"]).is_err());
+    }
+
+    #[test]
+    fn prompt_overrides_config() {
+        let mut config = AppConfig {
+            prompt: "from config".to_string(),
+            ..Default::default()
+        };
+        Args::default().apply(&mut config);
+        assert_eq!(config.prompt, "from config");
+
+        Args::parse_from(["--prompt", "Run:"])
+            .unwrap()
+            .apply(&mut config);
+        assert_eq!(config.prompt, "Run:");
+    }
+
+    #[test]
+    fn parses_open() {
+        let args = Args::parse_from(["--open", "/tmp/cat.png"]).unwrap();
+        assert_eq!(args.open.as_deref(), Some("/tmp/cat.png"));
+        assert!(Args::parse_from(["--open"]).is_err());
+        assert!(Args::parse_from(["--open", "a.png", "--stdin"]).is_err());
+    }
+
+    #[test]
+    fn parses_profile() {
+        let args = Args::parse_from(["--profile", "bar"]).unwrap();
+        assert_eq!(args.profile.as_deref(), Some("bar"));
+        assert!(Args::parse_from(["--profile"]).is_err());
+        assert!(Args::parse_from(["--profile", ""]).is_err());
+        assert!(Args::parse_from(["--profile", "../bar"]).is_err());
+    }
+
+    #[test]
+    fn takes_dmenu_flags() {
+        let args =
+            Args::parse_from(["-l", "10", "-b", "-i", "-fn", "Mono-12", "-p", "Run:"]).unwrap();
+        assert_eq!(args.lines, Some(10));
+        assert!(args.bottom);
+        assert_eq!(args.prompt.as_deref(), Some("Run:"));
+        assert!(Args::parse_from(["-l"]).is_err());
+        assert!(Args::parse_from(["-l", "ten"]).is_err());
+        assert!(Args::parse_from(["-nb"]).is_err());
+        assert!(Args::parse_from(["-v"]).unwrap().version);
+
+        let mut config = AppConfig {
+            layout: Layout::Horizontal,
+            ..Default::default()
+        };
+        args.apply(&mut config);
+        assert_eq!(config.lines, 10);
+        assert_eq!(config.layout, Layout::Vertical);
+        assert!(config.layer_shell.enabled);
+        assert_eq!(config.layer_shell.anchor, Anchor::Bottom);
+    }
+
+    #[test]
+    fn parses_fields() {
+        let args = Args::parse_from(["--stdin", "--with-fields"]).unwrap();
+        assert!(args.with_fields);
+        assert_eq!(args.delimiter, None);
+        let args = Args::parse_from(["--stdin", "--with-fields", "--delimiter", "|"]).unwrap();
+        assert_eq!(args.delimiter.as_deref(), Some("|"));
+
+        assert!(Args::parse_from(["--with-fields"]).is_err());
+        assert!(Args::parse_from(["--stdin", "--with-fields", "--index"]).is_err());
+        assert!(Args::parse_from(["--stdin", "--delimiter", "|"]).is_err());
+        assert!(Args::parse_from(["--stdin", "--with-fields", "--delimiter", ""]).is_err());
+    }
+
+    #[test]
+    fn index_requires_stdin() {
+        assert!(Args::parse_from(["--index"]).is_err());
+        assert!(Args::parse_from(["--index", "--stdin"]).unwrap().index);
+    }
+
+    #[test]
+    fn help_wins_over_invalid_combinations() {
+        assert!(Args::parse_from(["--index", "--help"]).unwrap().help);
+        assert!(Args::parse_from(["-V"]).unwrap().version);
+        assert!(Args::parse_from(["--help", "--bogus"]).is_err());
+    }
+
+    #[test]
+    fn help_lists_every_flag() {
+        for flag in [
+            "--stdin",
+            "--with-fields",
+            "--delimiter",
+            "--index",
+            "--print",
+            "--dry-run",
+            "--clipboard",
+            "--multi",
+            "--password",
+            "--shell",
+            "--open",
+            "--profile",
+            "--prompt",
+            "-l",
+            "-b",
+            "--help",
+            "--version",
+        ] {
+            assert!(HELP.contains(flag), "{} is missing from the help", flag);
+            let args = match flag {
+                "--delimiter" => vec!["--stdin", "--with-fields", flag, ","],
+                "--open" | "--profile" | "--prompt" => vec![flag, "x"],
+                "-l" => vec![flag, "5"],
+                "--with-fields" | "--index" => vec!["--stdin", flag],
+                _ => vec![flag],
+            };
+            assert!(Args::parse_from(args).is_ok(), "{} doesn't parse", flag);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert!(Args::parse_from(["--bogus"]).is_err());
+    }
+}
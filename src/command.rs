@@ -1,9 +1,33 @@
+use crate::desktop_entry::{DesktopAction, DesktopEntry};
+use crate::exec::{expand_exec, join_args};
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
 #[derive(Debug)]
 /// Represents a command that is selectable in the menu
 pub struct Command {
     key: String,
     display: String,
     command: String,
+    icon: Option<String>,
+    categories: Vec<String>,
+    /// Whether the desktop entry asks to be started over D-Bus
+    dbus_activatable: bool,
+    /// The desktop entry's `Path`, to run the command in
+    working_dir: Option<String>,
+    /// Whether the desktop entry has `StartupNotify=true`
+    startup_notify: bool,
+    /// Whether the desktop entry has `Terminal=true`, to run in a terminal
+    terminal: bool,
+    /// The desktop entry's `StartupWMClass`, the class its windows will have
+    startup_wm_class: Option<String>,
+    /// The application's desktop actions, for picking one from the entry
+    actions: Vec<Command>,
+    /// The desktop entry's localized `GenericName`, e.g. "Web Browser"
+    generic_name: Option<String>,
+    /// The desktop entry's localized `Keywords`
+    keywords: Vec<String>,
 }
 
 impl Command {
@@ -18,7 +42,118 @@ impl Command {
             key: key.into(),
             display: display.into(),
             command: command.into(),
+            icon: None,
+            categories: Vec::new(),
+            dbus_activatable: false,
+            working_dir: None,
+            startup_notify: false,
+            terminal: false,
+            startup_wm_class: None,
+            actions: Vec::new(),
+            generic_name: None,
+            keywords: Vec::new(),
+        }
+    }
+
+    /// Starts building a Command field by field
+    pub fn builder() -> CommandBuilder {
+        CommandBuilder::default()
+    }
+
+    /// Creates a Command from a `key`, `display` and `command` line split on
+    /// `delimiter`, like `firefox<TAB>Firefox<TAB>firefox --new-window`.
+    /// Missing or empty display and command fields default to the key, so a
+    /// line without the delimiter is the same as [`Command::from`].
+    pub fn from_fields(line: &str, delimiter: &str) -> Command {
+        let mut fields = line.splitn(3, delimiter);
+        let mut builder = Command::builder().key(fields.next().unwrap_or_default());
+        if let Some(display) = fields.next().filter(|f| !f.is_empty()) {
+            builder = builder.display(display);
+        }
+        if let Some(command) = fields.next().filter(|f| !f.is_empty()) {
+            builder = builder.command(command);
         }
+        builder.build()
+    }
+
+    /// Creates a Command for an application's `[Desktop Entry]`, keyed on its
+    /// desktop file ID and carrying its icon and categories. Returns `None` for
+    /// entries that can't be launched.
+    pub fn from_desktop_entry(id: &str, entry: &DesktopEntry, locale: &str) -> Option<Command> {
+        Command::from_desktop_entry_opening(id, entry, locale, &[])
+    }
+
+    /// Like [`Command::from_desktop_entry`], but the command opens `files`,
+    /// which are put in place of the Exec field codes.
+    pub fn from_desktop_entry_opening(
+        id: &str,
+        entry: &DesktopEntry,
+        locale: &str,
+        files: &[&str],
+    ) -> Option<Command> {
+        if entry.entry_type != "Application" {
+            return None;
+        }
+        let name = entry.name.get_for_locale(locale)?;
+        let icon = entry.icon.as_ref().and_then(|i| i.get_for_locale(locale));
+        let argv = expand_exec(entry.exec.as_deref()?, files, icon, name).ok()?;
+        Some(Command {
+            icon: icon.map(str::to_string),
+            categories: categories(entry),
+            // Activating over D-Bus wouldn't pass the files along
+            dbus_activatable: entry.dbus_activatable == Some(true) && files.is_empty(),
+            working_dir: entry.path.clone(),
+            startup_notify: entry.startup_notify == Some(true),
+            terminal: entry.terminal == Some(true),
+            startup_wm_class: entry.startup_wm_class.clone(),
+            generic_name: entry.generic_name(locale).map(str::to_string),
+            keywords: entry
+                .keywords(locale)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            ..Command::new(id, name, join_args(&argv))
+        })
+    }
+
+    /// Creates a Command for one of an application's `[Desktop Action]`s,
+    /// shown as "App: Action" and keyed on `<id>::<action_id>`. Actions without
+    /// their own icon inherit the entry's, and all share its categories.
+    pub fn from_desktop_action(
+        id: &str,
+        entry: &DesktopEntry,
+        action_id: &str,
+        action: &DesktopAction,
+        locale: &str,
+    ) -> Option<Command> {
+        let name = entry.name.get_for_locale(locale)?;
+        let action_name = action.name.get_for_locale(locale)?;
+        let icon = action
+            .icon
+            .as_ref()
+            .or(entry.icon.as_ref())
+            .and_then(|i| i.get_for_locale(locale));
+        let argv = expand_exec(action.exec.as_deref()?, &[], icon, name).ok()?;
+        Some(Command {
+            icon: icon.map(str::to_string),
+            categories: categories(entry),
+            dbus_activatable: entry.dbus_activatable == Some(true),
+            working_dir: entry.path.clone(),
+            startup_notify: entry.startup_notify == Some(true),
+            terminal: entry.terminal == Some(true),
+            startup_wm_class: entry.startup_wm_class.clone(),
+            ..Command::new(
+                format!("{}::{}", id, action_id),
+                format!("{}: {}", name, action_name),
+                join_args(&argv),
+            )
+        })
+    }
+
+    /// Attaches the application's desktop actions, see [`Command::actions`]
+    pub fn with_actions(mut self, actions: Vec<Command>) -> Command {
+        self.actions = actions;
+        self
     }
 
     /// Returns the key
@@ -33,12 +168,92 @@ impl Command {
     pub fn command(&self) -> &str {
         &self.command
     }
+    /// Returns the icon name or path, if any
+    pub fn icon(&self) -> Option<&str> {
+        self.icon.as_deref()
+    }
+    /// Returns the desktop entry categories, empty if there are none
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+    /// Returns whether the command should be started over D-Bus
+    pub fn dbus_activatable(&self) -> bool {
+        self.dbus_activatable
+    }
+    /// Returns the directory to run the command in, if it has one
+    pub fn working_dir(&self) -> Option<&str> {
+        self.working_dir.as_deref()
+    }
+    /// Returns whether launching should send startup notification
+    pub fn startup_notify(&self) -> bool {
+        self.startup_notify
+    }
+    /// Returns whether the command has to run in a terminal
+    pub fn terminal(&self) -> bool {
+        self.terminal
+    }
+    /// Returns the window class the application's windows are expected to
+    /// have, if the entry says
+    pub fn startup_wm_class(&self) -> Option<&str> {
+        self.startup_wm_class.as_deref()
+    }
+    /// Returns the desktop entry's `GenericName`, if it has one
+    pub fn generic_name(&self) -> Option<&str> {
+        self.generic_name.as_deref()
+    }
+    /// Returns the desktop entry's `Keywords`, empty if there are none
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+    /// Returns the application's desktop actions, each runnable on its own.
+    /// Empty for anything but an application's entry.
+    pub fn actions(&self) -> &[Command] {
+        &self.actions
+    }
 }
 
-impl Into<String> for Command {
-    /// Returns a string representation
-    fn into(self) -> String {
-        self.display.clone()
+fn categories(entry: &DesktopEntry) -> Vec<String> {
+    entry
+        .categories
+        .as_ref()
+        .map_or_else(Vec::new, |c| c.0.clone())
+}
+
+/// Commands are identified by their key alone
+impl PartialEq for Command {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Command {}
+
+impl Hash for Command {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+/// Drops every command whose key was already seen, keeping the first one's
+/// display text and command.
+pub fn dedup_commands(cmds: Vec<Command>) -> Vec<Command> {
+    let mut seen = HashSet::new();
+    cmds.into_iter()
+        .filter(|cmd| seen.insert(cmd.key.clone()))
+        .collect()
+}
+
+impl fmt::Display for Command {
+    /// Writes the display text, as shown in the menu
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display)
+    }
+}
+
+impl From<Command> for String {
+    /// Returns the display text, same as `to_string()` without the copy
+    fn from(command: Command) -> String {
+        command.display
     }
 }
 
@@ -55,6 +270,176 @@ impl Clone for Command {
             key: self.key.clone(),
             display: self.display.clone(),
             command: self.command.clone(),
+            icon: self.icon.clone(),
+            categories: self.categories.clone(),
+            dbus_activatable: self.dbus_activatable,
+            working_dir: self.working_dir.clone(),
+            startup_notify: self.startup_notify,
+            terminal: self.terminal,
+            startup_wm_class: self.startup_wm_class.clone(),
+            actions: self.actions.clone(),
+            generic_name: self.generic_name.clone(),
+            keywords: self.keywords.clone(),
+        }
+    }
+}
+
+/// Builds a Command without relying on argument order. The display text and
+/// command default to the key when they aren't set.
+#[derive(Debug, Default, Clone)]
+pub struct CommandBuilder {
+    key: String,
+    display: Option<String>,
+    command: Option<String>,
+    icon: Option<String>,
+    categories: Vec<String>,
+}
+
+impl CommandBuilder {
+    /// Sets the key that identifies the command
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+    /// Sets the text shown in the menu
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+        self
+    }
+    /// Sets the command line that is run
+    pub fn command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+    /// Sets the icon name or path
+    pub fn with_icon(mut self, icon: impl Into<String>) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+    /// Adds a category
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    /// Finishes the Command
+    pub fn build(self) -> Command {
+        Command {
+            display: self.display.unwrap_or_else(|| self.key.clone()),
+            command: self.command.unwrap_or_else(|| self.key.clone()),
+            key: self.key,
+            icon: self.icon,
+            categories: self.categories,
+            dbus_activatable: false,
+            working_dir: None,
+            startup_notify: false,
+            terminal: false,
+            startup_wm_class: None,
+            actions: Vec::new(),
+            generic_name: None,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop_entry::DesktopFile;
+
+    #[test]
+    fn formats_as_the_display_text() {
+        let command = Command::new("firefox.desktop", "Firefox", "firefox");
+        assert_eq!(format!("{}", command), "Firefox");
+        assert_eq!(String::from(command), "Firefox");
+    }
+
+    #[test]
+    fn builder_defaults_display_and_command_to_the_key() {
+        let command = Command::builder().key("htop").build();
+        assert_eq!(command.key(), "htop");
+        assert_eq!(command.display(), "htop");
+        assert_eq!(command.command(), "htop");
+
+        let command = Command::builder()
+            .key("firefox.desktop")
+            .display("Firefox")
+            .build();
+        assert_eq!(command.display(), "Firefox");
+        assert_eq!(command.command(), "firefox.desktop");
+
+        let command = Command::builder()
+            .command("firefox")
+            .key("firefox.desktop")
+            .build();
+        assert_eq!(command.display(), "firefox.desktop");
+        assert_eq!(command.command(), "firefox");
+    }
+
+    #[test]
+    fn from_fields_fills_in_missing_fields_from_the_key() {
+        let command = Command::from_fields("htop", "\t");
+        assert_eq!(command.key(), "htop");
+        assert_eq!(command.display(), "htop");
+        assert_eq!(command.command(), "htop");
+
+        let command = Command::from_fields("ff\tFirefox", "\t");
+        assert_eq!(command.key(), "ff");
+        assert_eq!(command.display(), "Firefox");
+        assert_eq!(command.command(), "ff");
+
+        let command = Command::from_fields("ff\tFirefox\tfirefox --new-window\tx", "\t");
+        assert_eq!(command.key(), "ff");
+        assert_eq!(command.display(), "Firefox");
+        assert_eq!(command.command(), "firefox --new-window\tx");
+
+        let command = Command::from_fields("ff||firefox", "|");
+        assert_eq!(command.display(), "ff");
+        assert_eq!(command.command(), "firefox");
+    }
+
+    #[test]
+    fn dedup_keeps_the_first_of_each_key() {
+        let cmds = vec![
+            Command::new("a", "First A", "a1"),
+            Command::from("b".to_string()),
+            Command::new("a", "Second A", "a2"),
+            Command::from("b".to_string()),
+        ];
+        let deduped = dedup_commands(cmds);
+        assert_eq!(deduped.len(), 2);
+        assert_eq!(deduped[0].display(), "First A");
+        assert_eq!(deduped[0].command(), "a1");
+        assert_eq!(deduped[1].key(), "b");
+    }
+
+    #[test]
+    fn carries_startup_metadata_from_the_entry() {
+        let file = DesktopFile::parse(
+            "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox %u\n\
+             StartupNotify=true\nStartupWMClass=firefox\n",
+        )
+        .unwrap();
+        let command =
+            Command::from_desktop_entry("firefox.desktop", file.entry().unwrap(), "C").unwrap();
+        assert!(command.startup_notify());
+        assert_eq!(command.startup_wm_class(), Some("firefox"));
+
+        let command = Command::new("htop", "htop", "htop");
+        assert!(!command.startup_notify());
+        assert_eq!(command.startup_wm_class(), None);
+    }
+
+    #[test]
+    fn entries_with_an_empty_exec_are_dropped() {
+        for exec in ["", "   "] {
+            let file = DesktopFile::parse(&format!(
+                "[Desktop Entry]\nType=Application\nName=Broken\nExec={}\n",
+                exec
+            ))
+            .unwrap();
+            let entry = file.entry().unwrap();
+            assert!(Command::from_desktop_entry("broken.desktop", entry, "C").is_none());
         }
     }
 }
@@ -1,70 +1,1078 @@
+use crate::core::{FrecencyKey, SearchField, ShortQuery, SortMode};
+use crate::keybindings;
+use crate::matcher::MatchMode;
+use crate::sources::SourceSpec;
 use directories::ProjectDirs;
-use ron::de::from_str;
-use ron::ser::{PrettyConfig, to_string_pretty};
-use serde::{Deserialize, Serialize};
+use log::{debug, warn};
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+/// The layout version of the config files this build writes, see
+/// [`AppConfig::migrate`]
+pub const CONFIG_VERSION: u32 = 2;
+
+/// The version of a file without a `version` key, all of which predate it
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Colors are RGB components in `0.0..=1.0`, or `"#rrggbb"` strings.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct ColorsConfig {
+    /// The layout the file was written for, see [`CONFIG_VERSION`]
+    #[serde(default = "legacy_version")]
+    pub version: u32,
+    #[serde(deserialize_with = "deserialize_color")]
     pub background: [f32; 3],
+    #[serde(deserialize_with = "deserialize_color")]
     pub text: [f32; 3],
+    #[serde(deserialize_with = "deserialize_color")]
     pub highlight: [f32; 3],
     pub font_size: f32,
+    /// How opaque the window is, from `MIN_OPACITY` to `1.0`. Anything less
+    /// than `1.0` only shows through with a compositor running; without one
+    /// the window is drawn opaque.
+    pub opacity: f32,
 }
 
+/// The lowest `ColorsConfig::opacity`, so the window can't vanish entirely
+pub const MIN_OPACITY: f32 = 0.2;
+
 impl Default for ColorsConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             background: [0.1, 0.1, 0.1],
             text: [1.0, 1.0, 1.0],
             highlight: [0.3, 0.3, 0.7],
             font_size: 16.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl ColorsConfig {
+    /// Upgrades a config written for an older `version` to the current
+    /// layout, see [`AppConfig::migrate`]
+    pub fn migrate(&mut self) {
+        // 1 -> 2: only new keys, which loading already defaulted
+        self.version = self.version.max(CONFIG_VERSION);
+    }
+
+    /// Clamps every color component into `0.0..=1.0` and the opacity into
+    /// `MIN_OPACITY..=1.0`, warning about each key that was out of range.
+    pub fn validate(&mut self) {
+        let colors = [
+            ("background", &mut self.background),
+            ("text", &mut self.text),
+            ("highlight", &mut self.highlight),
+        ];
+        for (key, color) in colors {
+            if color.iter().all(|c| (0.0..=1.0).contains(c)) {
+                continue;
+            }
+            warn!(
+                "colors.{} = {:?} is outside 0.0..=1.0, clamping",
+                key, color
+            );
+            for c in color.iter_mut() {
+                *c = if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) };
+            }
+        }
+        if !(MIN_OPACITY..=1.0).contains(&self.opacity) {
+            let clamped = if self.opacity.is_nan() {
+                1.0
+            } else {
+                self.opacity.clamp(MIN_OPACITY, 1.0)
+            };
+            warn!(
+                "colors.opacity = {} is outside {}..=1.0, using {}",
+                self.opacity, MIN_OPACITY, clamped
+            );
+            self.opacity = clamped;
+        }
+    }
+}
+
+/// Parses a `"#rrggbb"` (or `"rrggbb"`) hex string into RGB components.
+pub fn from_hex(hex: &str) -> Option<[f32; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let mut rgb = [0.0; 3];
+    for (i, c) in rgb.iter_mut().enumerate() {
+        let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        *c = byte as f32 / 255.0;
+    }
+    Some(rgb)
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<[f32; 3], D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Color {
+        Rgb([f32; 3]),
+        Hex(String),
+    }
+
+    match Color::deserialize(deserializer)? {
+        Color::Rgb(rgb) => Ok(rgb),
+        Color::Hex(hex) => {
+            from_hex(&hex).ok_or_else(|| de::Error::custom(format!("invalid hex color: {}", hex)))
+        }
+    }
+}
+
+/// How the results are laid out in the window
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// A list below the input field
+    #[default]
+    Vertical,
+    /// A single dmenu-style bar spanning the screen width, with the results
+    /// to the right of the input field
+    Horizontal,
+}
+
+/// Where the input field goes in the vertical layout
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputPosition {
+    /// Above the results, best match first
+    #[default]
+    Top,
+    /// Below the results, with the best match just above it and the rest
+    /// growing upward. The arrow keys move the way they point on screen, so
+    /// Up moves away from the input to worse matches.
+    Bottom,
+}
+
+/// How entries too long for the window's width are shown in the vertical
+/// layout
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LabelMode {
+    /// Run past the edge of the window
+    #[default]
+    Full,
+    /// Cut off with `…` at the edge
+    Truncate,
+    /// Continue on the next line, so rows vary in height. `lines` counts them
+    /// as one line each when sizing the window.
+    Wrap,
+}
+
+/// Where the window is placed on screen
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionMode {
+    /// At `position`, in pixels
+    #[default]
+    Absolute,
+    /// Centered on the primary monitor
+    Center,
+    /// Horizontally centered at the top of the primary monitor
+    TopCenter,
+    /// In the top-left corner of the primary monitor
+    TopLeft,
+    /// In the top-right corner of the primary monitor
+    TopRight,
+    /// In the bottom-left corner of the primary monitor
+    BottomLeft,
+    /// In the bottom-right corner of the primary monitor
+    BottomRight,
+}
+
+impl PositionMode {
+    /// The window's top-left corner for a window of `window` size on a monitor
+    /// of `monitor` size, kept `margin` pixels horizontally and vertically
+    /// from the edges it's placed against. Falls back to `absolute` when the
+    /// monitor size is unknown.
+    pub fn resolve(
+        self,
+        absolute: (f32, f32),
+        margin: (f32, f32),
+        monitor: Option<(f32, f32)>,
+        window: (f32, f32),
+    ) -> (f32, f32) {
+        let Some(monitor) = monitor else {
+            return absolute;
+        };
+        let center_x = ((monitor.0 - window.0) / 2.0).max(0.0);
+        let right = (monitor.0 - window.0 - margin.0).max(0.0);
+        let bottom = (monitor.1 - window.1 - margin.1).max(0.0);
+        match self {
+            PositionMode::Absolute => absolute,
+            PositionMode::Center => (center_x, ((monitor.1 - window.1) / 2.0).max(0.0)),
+            PositionMode::TopCenter => (center_x, margin.1),
+            PositionMode::TopLeft => margin,
+            PositionMode::TopRight => (right, margin.1),
+            PositionMode::BottomLeft => (margin.0, bottom),
+            PositionMode::BottomRight => (right, bottom),
+        }
+    }
+}
+
+/// The screen edge the window is anchored to
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Anchor {
+    /// The window's top-left corner when centered along the anchored edge,
+    /// `margin` pixels away from it.
+    pub fn resolve(self, margin: f32, monitor: (f32, f32), window: (f32, f32)) -> (f32, f32) {
+        let center_x = ((monitor.0 - window.0) / 2.0).max(0.0);
+        let center_y = ((monitor.1 - window.1) / 2.0).max(0.0);
+        match self {
+            Anchor::Top => (center_x, margin),
+            Anchor::Bottom => (center_x, (monitor.1 - window.1 - margin).max(0.0)),
+            Anchor::Left => (margin, center_y),
+            Anchor::Right => ((monitor.0 - window.0 - margin).max(0.0), center_y),
+        }
+    }
+}
+
+/// Anchoring the window to a screen edge, like a layer-shell overlay on
+/// wlroots compositors. Until rmenu can create layer-shell surfaces the window
+/// is moved to the anchored position instead, which overrides `position_mode`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct LayerShellConfig {
+    pub enabled: bool,
+    pub anchor: Anchor,
+    /// Distance from the anchored edge, in pixels
+    pub margin: f32,
+}
+
+impl Default for LayerShellConfig {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(feature = "wayland-layershell"),
+            anchor: Anchor::default(),
+            margin: 0.0,
         }
     }
 }
 
+/// The shortest `AppConfig::auto_accept_delay_ms`, comfortably above the
+/// pause between keystrokes of someone typing steadily
+pub const MIN_AUTO_ACCEPT_DELAY_MS: u64 = 250;
+
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct AppConfig {
+    /// The layout the file was written for, see [`CONFIG_VERSION`]
+    #[serde(default = "legacy_version")]
+    pub version: u32,
     pub position: (f32, f32),
+    /// Where the window is placed: at `position`, centered, or in a corner
+    /// `margin` away from the edges. Also read as `anchor`, e.g.
+    /// `anchor: TopRight`.
+    #[serde(alias = "anchor")]
+    pub position_mode: PositionMode,
+    /// Horizontal and vertical distance in pixels from the monitor edges the
+    /// window is placed against by `position_mode`
+    pub margin: (f32, f32),
+    /// A font egui knows by name, like the embedded `Ubuntu-M` or egui's own
+    /// `Hack`, or the path of a `.ttf`/`.otf` file to load. Without the
+    /// `bundled-font` feature `Ubuntu-M` isn't there, and egui's default font
+    /// is used instead.
     pub font_name: String,
+    /// `Alt+1`..`Alt+9` activate the Nth visible result. Set to `false` if
+    /// the modifier clashes with typing digits in queries.
+    pub alt_digit_hotkeys: bool,
+    pub layout: Layout,
+    /// Where the input field goes in the vertical layout
+    pub input_position: InputPosition,
+    pub label_mode: LabelMode,
+    /// Show the selected entry's command line along the bottom of the window.
+    /// Vertical layout only.
+    pub show_command_preview: bool,
+    /// List applications under category headings while the query is empty.
+    /// The headings are skipped when moving the selection.
+    pub group_by_category: bool,
+    pub match_mode: MatchMode,
+    /// Match the query against these too, not just the name, e.g.
+    /// `[GenericName, Keywords]` so "browser" finds Firefox. Matches of these
+    /// rank below equally good matches of the name.
+    pub search_fields: Vec<SearchField>,
+    /// Order of the applications while the query is empty. Matches for a
+    /// query are ranked by how well they match, then by frecency.
+    pub sort: SortMode,
+    /// Shown to the left of the input, like dmenu's `-p`. Empty for none.
+    pub prompt: String,
+    /// Also list each application's desktop actions, e.g. "Firefox: New
+    /// Private Window"
+    pub include_actions: bool,
+    /// List `OnlyShowIn` entries when `$XDG_CURRENT_DESKTOP` is unset or
+    /// empty, as under a bare window manager, instead of hiding them
+    pub show_only_show_in_when_unknown: bool,
+    /// Desktop file IDs of applications to leave out, like
+    /// `"libreoffice-startcenter.desktop"`. `*` matches any run of
+    /// characters, e.g. `"*-installer.desktop"`.
+    pub hide_ids: Vec<String>,
+    /// Unless empty, list only the applications with an ID matching one of
+    /// these, written like `hide_ids`. An ID matching both is hidden.
+    pub only_ids: Vec<String>,
+    /// More entries to list besides the applications or stdin, e.g.
+    /// `[Command(exec: "my-bookmarks")]`. Listed last, under "More" when
+    /// grouped.
+    pub sources: Vec<SourceSpec>,
+    /// What the history of picks is kept under. `Binary` or `WmClass` keep it
+    /// when a package renames its desktop file.
+    pub frecency_key: FrecencyKey,
+    /// Close the window when it loses focus, e.g. by clicking elsewhere
+    pub close_on_focus_loss: bool,
+    /// After launching an entry, clear the query and stay open for the next
+    /// one instead of closing. Printing options like `--print` still close.
+    /// Launched windows usually take the focus, so pair this with
+    /// `close_on_focus_loss: false`.
+    pub stay_open: bool,
+    /// Close the window after this many seconds without a key press or mouse
+    /// movement. `None` keeps it open.
+    pub idle_timeout_secs: Option<u64>,
+    /// Launch the only application left matching a typed query once the query
+    /// has stayed the same for `auto_accept_delay_ms`, if that's set, without
+    /// Enter. Typed commands, modes and the calculator are never launched
+    /// this way.
+    pub auto_accept_single: bool,
+    /// How long the query must stay unchanged before `auto_accept_single`
+    /// launches, in milliseconds, at least `MIN_AUTO_ACCEPT_DELAY_MS`. `None`
+    /// leaves it to Enter, which launches a lone match since it's selected.
+    pub auto_accept_delay_ms: Option<u64>,
+    /// When nothing matches, offer to run the query through `$SHELL -c`, like
+    /// the `:` prefix does
+    pub exec_fallback: bool,
+    /// The cancel key clears a non-empty query instead of closing, so a
+    /// second press is needed to close
+    pub escape_clears_first: bool,
+    /// Keys for `move_up`, `move_down`, `accept`, `cancel`, `page_up`,
+    /// `page_down`, `toggle_mark`, `show_actions`, `edit_config` and
+    /// `forget_entry`, and the line editing `clear_line`, `delete_word`,
+    /// `line_start` and `line_end`, written like `"Ctrl+J"`. Actions left out
+    /// keep their default keys, an empty list turns one off.
+    pub keybindings: BTreeMap<String, Vec<String>>,
+    pub layer_shell: LayerShellConfig,
+    /// Most result rows in view at once. The window grows to fit up to this
+    /// many and shrinks when fewer match. `0` keeps the window's own height.
+    /// Vertical layout only, and not while category headings are listed.
+    pub lines: usize,
+    /// Most matches listed at once, best first. `0` lists all of them.
+    pub max_results: usize,
+    /// Only match queries at least this many characters long, which keeps
+    /// typing responsive with very many entries. Shorter ones list what
+    /// `short_query` says.
+    pub min_query_len: usize,
+    pub short_query: ShortQuery,
+    /// Start with the last submitted query filled in and selected, so typing
+    /// replaces it
+    pub remember_query: bool,
+    /// Reopen the window where it was when it last closed, e.g. after
+    /// dragging it, instead of at `position` and `position_mode`. The place
+    /// is kept in rmenu's state directory rather than written back to
+    /// `position`, so `app.ron` stays as it was written and its `position`
+    /// is used again once this is turned off.
+    pub remember_position: bool,
+    /// Height of each result row in points. `None` fits the rows to the font.
+    pub row_height: Option<f32>,
+    /// Space between result rows in points
+    pub row_padding: f32,
+    /// Run selected commands through `$SHELL -c` instead of splitting them
+    /// into arguments. Only for input you trust, see `launcher::shell_argv`.
+    pub shell: bool,
+    /// The editor `edit_config` opens `app.ron` in when `$EDITOR` isn't set
+    pub editor: String,
+    /// The terminal the editor and `Terminal=true` entries run in, e.g.
+    /// `"foot"` or `"~/bin/foot"`, see [`expand_path`]. Known terminals get
+    /// the arguments they need to run a program, while one given with
+    /// arguments, like `"alacritty -e"`, is used as is. Empty picks
+    /// `$TERMINAL` or an installed one, see `launcher::find_terminal`.
+    pub terminal: String,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             position: (100.0, 100.0),
+            position_mode: PositionMode::default(),
+            margin: (0.0, 0.0),
             font_name: "Ubuntu-M".to_string(),
+            alt_digit_hotkeys: true,
+            layout: Layout::default(),
+            input_position: InputPosition::default(),
+            label_mode: LabelMode::default(),
+            show_command_preview: false,
+            group_by_category: false,
+            match_mode: MatchMode::default(),
+            search_fields: Vec::new(),
+            sort: SortMode::default(),
+            prompt: String::new(),
+            include_actions: true,
+            show_only_show_in_when_unknown: false,
+            hide_ids: Vec::new(),
+            only_ids: Vec::new(),
+            sources: Vec::new(),
+            frecency_key: FrecencyKey::default(),
+            close_on_focus_loss: true,
+            stay_open: false,
+            idle_timeout_secs: None,
+            auto_accept_single: false,
+            auto_accept_delay_ms: None,
+            exec_fallback: false,
+            escape_clears_first: false,
+            keybindings: keybindings::default_config(),
+            layer_shell: LayerShellConfig::default(),
+            lines: 0,
+            max_results: 50,
+            min_query_len: 0,
+            short_query: ShortQuery::default(),
+            remember_query: false,
+            remember_position: false,
+            row_height: None,
+            row_padding: 3.0,
+            shell: false,
+            editor: "vi".to_string(),
+            terminal: String::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Upgrades a config written for an older `version` to the current
+    /// layout, keeping the values it set. Keys added since are filled in with
+    /// their defaults while loading, so this only has to move renamed keys or
+    /// convert reinterpreted values, one version step at a time.
+    pub fn migrate(&mut self) {
+        if self.version < CONFIG_VERSION {
+            debug!(
+                "migrating the app config from version {} to {}",
+                self.version, CONFIG_VERSION
+            );
+        }
+        // 1 -> 2: only new keys, which loading already defaulted
+        self.version = self.version.max(CONFIG_VERSION);
+    }
+
+    /// Moves a negative or non-finite `position` back onto the screen's
+    /// coordinates with a warning, since such a window can't be seen or
+    /// closed. The monitor layout isn't known here, so only the lower
+    /// bound is checked. An `auto_accept_delay_ms` too short to outlast a
+    /// pause between keystrokes is raised to `MIN_AUTO_ACCEPT_DELAY_MS`.
+    pub fn validate(&mut self) {
+        let (x, y) = self.position;
+        if !(x.is_finite() && y.is_finite() && x >= 0.0 && y >= 0.0) {
+            let clamp = |c: f32| if c.is_finite() { c.max(0.0) } else { 0.0 };
+            let clamped = (clamp(x), clamp(y));
+            warn!(
+                "position = {:?} is off screen, using {:?}",
+                self.position, clamped
+            );
+            self.position = clamped;
+        }
+        if let Some(delay) = self.auto_accept_delay_ms
+            && delay < MIN_AUTO_ACCEPT_DELAY_MS
+        {
+            warn!(
+                "auto_accept_delay_ms = {} would launch while typing, using {}",
+                delay, MIN_AUTO_ACCEPT_DELAY_MS
+            );
+            self.auto_accept_delay_ms = Some(MIN_AUTO_ACCEPT_DELAY_MS);
+        }
+    }
+}
+
+/// Expands a leading `~` to `$HOME` and `$VAR` or `${VAR}` to the
+/// variable's value, for paths in config values and desktop entries. Unset
+/// variables are left as written, so a typo shows up in the path instead of
+/// silently turning it into a different one.
+pub fn expand_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+    if (rest == "~" || rest.starts_with("~/"))
+        && let Ok(home) = env::var("HOME")
+    {
+        out.push_str(&home);
+        rest = &rest[1..];
+    }
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let written = &rest[dollar..dollar + 1 + len];
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            _ => out.push_str(written),
         }
+        rest = &rest[dollar + 1 + len..];
     }
+    out.push_str(rest);
+    out
+}
+
+/// The serde backend a config file is read and written with, picked by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ron,
+    Toml,
+    Json,
 }
 
-pub fn get_config_paths() -> Option<(PathBuf, PathBuf)> {
+impl Format {
+    const EXTENSIONS: [&str; 3] = ["ron", "toml", "json"];
+
+    /// RON unless the extension says otherwise
+    fn of(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("json") => Format::Json,
+            _ => Format::Ron,
+        }
+    }
+
+    fn parse<T: for<'de> Deserialize<'de>>(self, content: &str) -> Result<T, String> {
+        match self {
+            Format::Ron => ron::from_str(content).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Option<String> {
+        match self {
+            Format::Ron => ron::ser::to_string_pretty(value, PrettyConfig::default()).ok(),
+            Format::Toml => toml::to_string_pretty(value).ok(),
+            Format::Json => serde_json::to_string_pretty(value).ok(),
+        }
+    }
+}
+
+/// Paths of the colors and app config files, see [`config_paths_in`].
+pub fn get_config_paths(profile: Option<&str>) -> Option<(PathBuf, PathBuf)> {
     let proj_dirs = ProjectDirs::from("com", "example", "rmenu")?;
     let config_dir = proj_dirs.config_dir();
     fs::create_dir_all(config_dir).ok()?;
-    let colors_path = config_dir.join("colors.ron");
-    let app_path = config_dir.join("app.ron");
-    Some((colors_path, app_path))
+    Some(config_paths_in(config_dir, profile))
 }
 
-pub fn load_config<T: Default + for<'de> Deserialize<'de>>(path: &PathBuf) -> T {
-    if let Ok(mut file) = fs::File::open(path) {
-        let mut content = String::new();
-        if file.read_to_string(&mut content).is_ok() {
-            if let Ok(config) = from_str(&content) {
-                return config;
+/// Paths of the colors and app config files in `dir`. Each is whichever of
+/// `<name>.ron`, `<name>.toml` and `<name>.json` exists, in that order,
+/// defaulting to RON. With a `profile`, `<name>.<profile>.ron` and so on
+/// are looked for first, falling back to the files without one.
+pub fn config_paths_in(dir: &Path, profile: Option<&str>) -> (PathBuf, PathBuf) {
+    let profiled = |name: &str| profile.and_then(|p| find_config(dir, &format!("{}.{}", name, p)));
+    let (colors, app) = (profiled("colors"), profiled("app"));
+    if let Some(profile) = profile
+        && colors.is_none()
+        && app.is_none()
+    {
+        warn!(
+            "no config files for profile `{}`, using the default ones",
+            profile
+        );
+    }
+    let or_default = |found: Option<PathBuf>, name: &str| {
+        found
+            .or_else(|| find_config(dir, name))
+            .unwrap_or_else(|| dir.join(format!("{}.ron", name)))
+    };
+    (or_default(colors, "colors"), or_default(app, "app"))
+}
+
+/// The first of `<name>.ron`, `<name>.toml` and `<name>.json` in `dir` that exists
+fn find_config(dir: &Path, name: &str) -> Option<PathBuf> {
+    Format::EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.exists())
+}
+
+/// Directories searched for system-wide config, least important first:
+/// `/etc/rmenu`, then each of `$XDG_CONFIG_DIRS` (default `/etc/xdg`) with
+/// `rmenu` appended, in reverse order.
+pub fn system_config_dirs() -> Vec<PathBuf> {
+    let xdg_dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    let mut dirs: Vec<PathBuf> = std::env::split_paths(&xdg_dirs)
+        .filter(|d| d.is_absolute())
+        .map(|d| d.join("rmenu"))
+        .collect();
+    dirs.push(PathBuf::from("/etc/rmenu"));
+    dirs.reverse();
+    dirs
+}
+
+/// System-wide config files with the same name as `path`, least important first
+pub fn system_config_files(path: &Path) -> Vec<PathBuf> {
+    let Some(name) = path.file_stem().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    system_config_dirs()
+        .iter()
+        .filter_map(|dir| find_config(dir, name))
+        .collect()
+}
+
+/// Loads the system-wide config files named like `path`, then `path` itself on
+/// top, field by field. Fields no file sets keep their `Default`.
+pub fn load_layered<T: Default + Serialize + DeserializeOwned>(path: &Path) -> T {
+    try_load_layered(path).unwrap_or_default()
+}
+
+/// Like `load_layered`, but returns `None` when `path` exists and doesn't
+/// parse. System files that don't parse are skipped with a warning.
+pub fn try_load_layered<T: Default + Serialize + DeserializeOwned>(path: &Path) -> Option<T> {
+    let mut layers = system_config_files(path);
+    layers.push(path.to_path_buf());
+    merge_layers(&layers)
+}
+
+fn merge_layers<T: Default + Serialize + DeserializeOwned>(layers: &[PathBuf]) -> Option<T> {
+    let (user, system) = layers.split_last()?;
+    let mut merged = serde_json::to_value(T::default()).ok()?;
+    for layer in system {
+        match load_layer::<T>(layer) {
+            Some((typed, raw)) => overlay(&mut merged, typed, &raw),
+            None => warn!("ignoring {}", layer.display()),
+        }
+    }
+    if user.exists() {
+        let (typed, raw) = load_layer::<T>(user)?;
+        overlay(&mut merged, typed, &raw);
+    }
+    serde_json::from_value(merged).ok()
+}
+
+/// A config file's contents as a `T`, with defaults filled in, and as it was
+/// written, which tells which fields it actually sets. Both are JSON so files
+/// in any format can be merged.
+fn load_layer<T: Serialize + DeserializeOwned>(path: &Path) -> Option<(Value, Value)> {
+    debug!("loading config {}", path.display());
+    let content = fs::read_to_string(path)
+        .inspect_err(|e| warn!("couldn't read {}: {}", path.display(), e))
+        .ok()?;
+    let format = Format::of(path);
+    let parsed = format.parse::<T>(&content).and_then(|typed| {
+        let typed = serde_json::to_value(typed).map_err(|e| e.to_string())?;
+        let mut raw = format.parse::<Value>(&content)?;
+        // A file without a version is an old one, which has to win over the
+        // current version of the defaults so it gets migrated
+        if let (Value::Object(raw), Some(version)) = (&mut raw, typed.get("version")) {
+            raw.entry("version").or_insert_with(|| version.clone());
+        }
+        Ok((typed, raw))
+    });
+    parsed
+        .inspect_err(|e| warn!("{} doesn't parse: {}", path.display(), e))
+        .ok()
+}
+
+/// Copies the fields of `typed` that `raw` sets onto `base`, recursing into
+/// nested structs and maps so they're merged key by key too.
+fn overlay(base: &mut Value, typed: Value, raw: &Value) {
+    match (base, typed, raw) {
+        (Value::Object(base), Value::Object(mut typed), Value::Object(raw)) => {
+            for (key, raw) in raw {
+                let Some(typed) = typed.remove(key) else {
+                    continue;
+                };
+                match base.get_mut(key) {
+                    Some(base) => overlay(base, typed, raw),
+                    None => {
+                        base.insert(key.clone(), typed);
+                    }
+                }
             }
         }
+        (base, typed, _) => *base = typed,
     }
-    T::default()
+}
+
+pub fn load_config<T: Default + for<'de> Deserialize<'de>>(path: &PathBuf) -> T {
+    try_load_config(path).unwrap_or_default()
+}
+
+/// Like `load_config`, but returns `None` instead of falling back to the defaults
+/// when the file is missing or doesn't parse. The format follows the file
+/// extension: `.toml`, `.json`, or RON for anything else.
+pub fn try_load_config<T: for<'de> Deserialize<'de>>(path: &PathBuf) -> Option<T> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    Format::of(path).parse(&content).ok()
 }
 
 pub fn save_config<T: Serialize>(path: &PathBuf, config: &T) {
-    if let Ok(serialized) = to_string_pretty(config, PrettyConfig::default()) {
-        if let Ok(mut file) = fs::File::create(path) {
-            let _ = file.write_all(serialized.as_bytes());
+    if let Some(serialized) = Format::of(path).serialize(config)
+        && let Ok(mut file) = fs::File::create(path)
+    {
+        let _ = file.write_all(serialized.as_bytes());
+    }
+}
+
+/// Polls the modification times of the config files so they can be reloaded live.
+pub struct ConfigWatcher {
+    colors_path: PathBuf,
+    app_path: PathBuf,
+    colors_mtime: Option<SystemTime>,
+    app_mtime: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(colors_path: PathBuf, app_path: PathBuf) -> Self {
+        Self {
+            colors_mtime: modified(&colors_path),
+            app_mtime: modified(&app_path),
+            colors_path,
+            app_path,
+        }
+    }
+
+    /// The user's `app.ron`
+    pub fn app_path(&self) -> &Path {
+        &self.app_path
+    }
+
+    /// Returns the colors config if `colors.ron` changed since the last poll and
+    /// parses cleanly. A half-written file is skipped until the next change.
+    pub fn poll_colors(&mut self) -> Option<ColorsConfig> {
+        let mut colors: ColorsConfig = poll(&self.colors_path, &mut self.colors_mtime)?;
+        colors.migrate();
+        colors.validate();
+        Some(colors)
+    }
+
+    /// Returns the app config if `app.ron` changed since the last poll and
+    /// parses cleanly.
+    pub fn poll_app(&mut self) -> Option<AppConfig> {
+        let mut app_config: AppConfig = poll(&self.app_path, &mut self.app_mtime)?;
+        app_config.migrate();
+        app_config.validate();
+        Some(app_config)
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn poll<T: Default + Serialize + DeserializeOwned>(
+    path: &PathBuf,
+    last: &mut Option<SystemTime>,
+) -> Option<T> {
+    let current = modified(path);
+    if current == *last {
+        return None;
+    }
+    *last = current;
+    try_load_layered(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(from_hex("#ff0000"), Some([1.0, 0.0, 0.0]));
+        assert_eq!(from_hex("00ff00"), Some([0.0, 1.0, 0.0]));
+        assert_eq!(from_hex("#fff"), None);
+        assert_eq!(from_hex("#gg0000"), None);
+    }
+
+    #[test]
+    fn deserializes_hex_and_rgb_colors() {
+        let colors: ColorsConfig = ron::from_str(
+            r##"(background: "#000000", text: (1.0, 1.0, 1.0), highlight: "#ffffff", font_size: 12.0)"##,
+        )
+        .unwrap();
+        assert_eq!(colors.background, [0.0, 0.0, 0.0]);
+        assert_eq!(colors.text, [1.0, 1.0, 1.0]);
+        assert_eq!(colors.highlight, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn anchor_is_read_as_the_position_mode() {
+        let config: AppConfig = ron::from_str("(anchor: TopRight, margin: (8.0, 16.0))").unwrap();
+        assert_eq!(config.position_mode, PositionMode::TopRight);
+        assert_eq!(config.margin, (8.0, 16.0));
+    }
+
+    #[test]
+    fn position_modes_resolve_against_the_monitor() {
+        let monitor = Some((1920.0, 1080.0));
+        let window = (600.0, 400.0);
+        let resolve = |mode: PositionMode, margin, monitor| {
+            mode.resolve((10.0, 20.0), margin, monitor, window)
+        };
+        let none = (0.0, 0.0);
+        assert_eq!(resolve(PositionMode::Absolute, none, monitor), (10.0, 20.0));
+        assert_eq!(resolve(PositionMode::Center, none, monitor), (660.0, 340.0));
+        assert_eq!(
+            resolve(PositionMode::TopCenter, none, monitor),
+            (660.0, 0.0)
+        );
+        assert_eq!(resolve(PositionMode::Center, none, None), (10.0, 20.0));
+
+        let margin = (8.0, 16.0);
+        assert_eq!(resolve(PositionMode::TopLeft, margin, monitor), (8.0, 16.0));
+        assert_eq!(
+            resolve(PositionMode::TopRight, margin, monitor),
+            (1312.0, 16.0)
+        );
+        assert_eq!(
+            resolve(PositionMode::BottomLeft, margin, monitor),
+            (8.0, 664.0)
+        );
+        assert_eq!(
+            resolve(PositionMode::BottomRight, margin, monitor),
+            (1312.0, 664.0)
+        );
+        assert_eq!(
+            resolve(PositionMode::TopCenter, margin, monitor),
+            (660.0, 16.0)
+        );
+        assert_eq!(
+            resolve(PositionMode::BottomRight, margin, None),
+            (10.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn colors_round_trip_in_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let colors = ColorsConfig {
+            background: [0.25, 0.5, 0.75],
+            font_size: 12.0,
+            ..Default::default()
+        };
+        for ext in Format::EXTENSIONS {
+            let path = dir.path().join(format!("colors.{}", ext));
+            save_config(&path, &colors);
+            let loaded: ColorsConfig = try_load_config(&path).unwrap();
+            assert_eq!(loaded.background, colors.background, "{}", ext);
+            assert_eq!(loaded.text, colors.text, "{}", ext);
+            assert_eq!(loaded.highlight, colors.highlight, "{}", ext);
+            assert_eq!(loaded.font_size, colors.font_size, "{}", ext);
+        }
+    }
+
+    #[test]
+    fn profiles_fall_back_to_the_default_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("colors.ron"), "()").unwrap();
+        fs::write(dir.path().join("app.ron"), "()").unwrap();
+        fs::write(dir.path().join("colors.bar.toml"), "").unwrap();
+
+        let (colors, app) = config_paths_in(dir.path(), Some("bar"));
+        assert_eq!(colors, dir.path().join("colors.bar.toml"));
+        assert_eq!(app, dir.path().join("app.ron"));
+
+        let unknown = config_paths_in(dir.path(), Some("nope"));
+        assert_eq!(unknown, config_paths_in(dir.path(), None));
+        assert_eq!(unknown.0, dir.path().join("colors.ron"));
+    }
+
+    #[test]
+    fn unversioned_configs_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.ron");
+        // As written before the version key, and before sort and lines
+        fs::write(
+            &path,
+            r#"(prompt: "Run:", max_results: 10, layout: Horizontal)"#,
+        )
+        .unwrap();
+        let mut config: AppConfig = merge_layers(std::slice::from_ref(&path)).unwrap();
+        assert_eq!(config.version, 1);
+
+        config.migrate();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.prompt, "Run:");
+        assert_eq!(config.max_results, 10);
+        assert_eq!(config.layout, Layout::Horizontal);
+        assert_eq!(config.sort, SortMode::default());
+        assert_eq!(config.lines, 0);
+        assert_eq!(config.keybindings, keybindings::default_config());
+
+        // New files say which version they are
+        save_config(&path, &AppConfig::default());
+        let config: AppConfig = merge_layers(&[path]).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn app_config_saves_as_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.toml");
+        save_config(&path, &AppConfig::default());
+        let loaded: Option<AppConfig> = try_load_config(&path);
+        assert_eq!(
+            loaded.unwrap().keybindings,
+            AppConfig::default().keybindings
+        );
+    }
+
+    #[test]
+    fn user_config_overrides_system_config_field_by_field() {
+        let system = tempfile::tempdir().unwrap();
+        let user = tempfile::tempdir().unwrap();
+        let system_path = system.path().join("app.ron");
+        let user_path = user.path().join("app.toml");
+        fs::write(
+            &system_path,
+            r#"(prompt: "system", layout: Horizontal, keybindings: {"cancel": ["Ctrl+G"]})"#,
+        )
+        .unwrap();
+        fs::write(
+            &user_path,
+            "layout = \"Vertical\"\n[keybindings]\naccept = [\"Ctrl+M\"]\n",
+        )
+        .unwrap();
+
+        let merged: AppConfig = merge_layers(&[system_path, user_path.clone()]).unwrap();
+        assert_eq!(merged.prompt, "system");
+        assert_eq!(merged.layout, Layout::Vertical);
+        assert_eq!(merged.keybindings["cancel"], ["Ctrl+G"]);
+        assert_eq!(merged.keybindings["accept"], ["Ctrl+M"]);
+        assert_eq!(merged.keybindings["move_up"], ["Up"]);
+        assert_eq!(merged.position, AppConfig::default().position);
+
+        fs::remove_file(&user_path).unwrap();
+        let defaults: AppConfig = merge_layers(&[user_path]).unwrap();
+        assert_eq!(defaults.prompt, AppConfig::default().prompt);
+    }
+
+    #[test]
+    fn unknown_extensions_are_ron() {
+        assert_eq!(Format::of(Path::new("colors.conf")), Format::Ron);
+        assert_eq!(Format::of(Path::new("colors")), Format::Ron);
+        assert_eq!(Format::of(Path::new("colors.toml")), Format::Toml);
+    }
+
+    #[test]
+    fn anchors_resolve_against_the_monitor() {
+        let monitor = (1920.0, 1080.0);
+        let window = (600.0, 400.0);
+        assert_eq!(Anchor::Top.resolve(10.0, monitor, window), (660.0, 10.0));
+        assert_eq!(
+            Anchor::Bottom.resolve(10.0, monitor, window),
+            (660.0, 670.0)
+        );
+        assert_eq!(Anchor::Left.resolve(10.0, monitor, window), (10.0, 340.0));
+        assert_eq!(
+            Anchor::Right.resolve(10.0, monitor, window),
+            (1310.0, 340.0)
+        );
+    }
+
+    #[test]
+    fn validate_clamps_out_of_range_components() {
+        let mut colors = ColorsConfig {
+            background: [-0.5, 0.5, 2.0],
+            text: [f32::NAN, 1.0, 1.0],
+            ..Default::default()
+        };
+        colors.validate();
+        assert_eq!(colors.background, [0.0, 0.5, 1.0]);
+        assert_eq!(colors.text, [0.0, 1.0, 1.0]);
+        assert_eq!(colors.highlight, ColorsConfig::default().highlight);
+    }
+
+    #[test]
+    fn validate_keeps_the_window_visible() {
+        for (opacity, expected) in [(0.5, 0.5), (0.0, MIN_OPACITY), (1.5, 1.0), (f32::NAN, 1.0)] {
+            let mut colors = ColorsConfig {
+                opacity,
+                ..Default::default()
+            };
+            colors.validate();
+            assert_eq!(colors.opacity, expected);
+        }
+    }
+
+    #[test]
+    fn expand_path_expands_home_and_variables() {
+        let home = env::var("HOME").unwrap();
+        let path = env::var("PATH").unwrap();
+        assert_eq!(
+            expand_path("~/fonts/a.ttf"),
+            format!("{}/fonts/a.ttf", home)
+        );
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("$PATH/x"), format!("{}/x", path));
+        assert_eq!(expand_path("a${PATH}b"), format!("a{}b", path));
+        // Left as written
+        for literal in [
+            "/opt/~user",
+            "$RMENU_UNSET_TEST_VAR/x",
+            "${RMENU_UNSET_TEST_VAR}",
+            "cost: $",
+            "${unclosed",
+            "$-x",
+        ] {
+            assert_eq!(expand_path(literal), literal);
+        }
+    }
+
+    #[test]
+    fn validate_keeps_auto_accept_from_firing_mid_word() {
+        for (delay, expected) in [
+            (None, None),
+            (Some(0), Some(MIN_AUTO_ACCEPT_DELAY_MS)),
+            (Some(800), Some(800)),
+        ] {
+            let mut config = AppConfig {
+                auto_accept_delay_ms: delay,
+                ..Default::default()
+            };
+            config.validate();
+            assert_eq!(config.auto_accept_delay_ms, expected);
+        }
+    }
+
+    #[test]
+    fn validate_moves_the_position_on_screen() {
+        for (position, expected) in [
+            ((100.0, 200.0), (100.0, 200.0)),
+            ((-50.0, 20.0), (0.0, 20.0)),
+            ((f32::NAN, f32::NEG_INFINITY), (0.0, 0.0)),
+        ] {
+            let mut config = AppConfig {
+                position,
+                ..Default::default()
+            };
+            config.validate();
+            assert_eq!(config.position, expected);
         }
     }
 }
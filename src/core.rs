@@ -0,0 +1,874 @@
+//! The menu's filtering and selection logic, kept apart from egui so it can
+//! be tested without a window.
+
+use crate::command::{Command, dedup_commands};
+use crate::exec::tokenize_exec;
+use crate::matcher::{Match, MatchMode, match_query};
+use crate::scanner;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+/// How the entries are ordered while the query is empty
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    /// Most used and most recently used first, then by name
+    #[default]
+    Frecency,
+    /// By name, ignoring case
+    Alphabetical,
+    /// By category heading as in the grouped view, then by name
+    CategoryThenName,
+}
+
+/// What's listed while the query is shorter than `AppConfig::min_query_len`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShortQuery {
+    /// The entries listed for an empty query
+    #[default]
+    Top,
+    /// Nothing, until enough is typed
+    Nothing,
+}
+
+/// Whether `query` is too short to be matched against the entries, being
+/// shorter than `min_len` characters. An empty query never is, it lists the
+/// entries as usual.
+pub fn too_short(query: &str, min_len: usize) -> bool {
+    !query.is_empty() && query.chars().count() < min_len
+}
+
+/// How much longer a query last typed at `typed_at` has to stay unchanged
+/// before a lone match is launched, see `AppConfig::auto_accept_single`.
+/// Times are in seconds, zero means it's due.
+pub fn settle_time(typed_at: f64, now: f64, delay_ms: u64) -> Duration {
+    let elapsed = Duration::try_from_secs_f64(now - typed_at).unwrap_or_default();
+    Duration::from_millis(delay_ms).saturating_sub(elapsed)
+}
+
+/// Orders two entries, each given with its frecency, for `mode`
+pub fn compare(mode: SortMode, a: (&Command, f64), b: (&Command, f64)) -> Ordering {
+    let by_name = || {
+        a.0.display()
+            .to_lowercase()
+            .cmp(&b.0.display().to_lowercase())
+    };
+    match mode {
+        SortMode::Frecency => b.1.total_cmp(&a.1).then_with(by_name),
+        SortMode::Alphabetical => by_name(),
+        SortMode::CategoryThenName => {
+            let category = |c: &Command| {
+                let heading = scanner::heading(c.categories());
+                (heading == "Other", heading)
+            };
+            category(a.0).cmp(&category(b.0)).then_with(by_name)
+        }
+    }
+}
+
+/// The indices of `entries` in `mode` order, at most `limit` of them, all if
+/// it's `0`. `frecency` is indexed like `entries`; missing values count as
+/// never used.
+pub fn sorted(entries: &[Command], frecency: &[f64], mode: SortMode, limit: usize) -> Vec<usize> {
+    let score = |i: usize| frecency.get(i).copied().unwrap_or(0.0);
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by(|&a, &b| compare(mode, (&entries[a], score(a)), (&entries[b], score(b))));
+    if limit > 0 {
+        order.truncate(limit);
+    }
+    order
+}
+
+/// The indices of `entries` listed for an empty query: in [`sorted`] order,
+/// or in the order they were loaded with `keep_order`.
+pub fn unfiltered(
+    entries: &[Command],
+    frecency: &[f64],
+    mode: SortMode,
+    limit: usize,
+    keep_order: bool,
+) -> Vec<usize> {
+    if !keep_order {
+        return sorted(entries, frecency, mode, limit);
+    }
+    let len = match limit {
+        0 => entries.len(),
+        limit => limit.min(entries.len()),
+    };
+    (0..len).collect()
+}
+
+/// What a query is matched against besides an entry's name, see
+/// `AppConfig::search_fields`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// The desktop entry's `GenericName`, like "Web Browser"
+    GenericName,
+    /// Each of the desktop entry's `Keywords`
+    Keywords,
+}
+
+/// The texts of `command` to match a query against: its display text first,
+/// then those of `fields` it has, see [`filtered_fields`].
+pub fn search_texts<'a>(command: &'a Command, fields: &[SearchField]) -> Vec<&'a str> {
+    let mut texts = vec![command.display()];
+    for field in fields {
+        match field {
+            SearchField::GenericName => texts.extend(command.generic_name()),
+            SearchField::Keywords => texts.extend(command.keywords().iter().map(String::as_str)),
+        }
+    }
+    texts
+}
+
+/// What picks of an entry are recorded under in the history, see
+/// `AppConfig::frecency_key`
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FrecencyKey {
+    /// The entry's key, the desktop file ID for applications
+    #[default]
+    Id,
+    /// The application's `StartupWMClass`
+    WmClass,
+    /// The file name of the program the entry runs, like `firefox`
+    Binary,
+}
+
+/// The history key for `command`. Entries without a WM class or program
+/// fall back to their own key. Desktop actions keep their action name on
+/// the end, so they're counted apart from their application.
+pub fn frecency_key(command: &Command, key: FrecencyKey) -> String {
+    let derived = match key {
+        FrecencyKey::Id => None,
+        FrecencyKey::WmClass => command.startup_wm_class().map(str::to_string),
+        FrecencyKey::Binary => tokenize_exec(command.command())
+            .ok()
+            .and_then(|argv| argv.into_iter().next())
+            .and_then(|program| {
+                let name = Path::new(&program).file_name()?;
+                Some(name.to_string_lossy().into_owned())
+            }),
+    };
+    match (derived, command.key().split_once("::")) {
+        (Some(derived), Some((_, action))) => format!("{}::{}", derived, action),
+        (Some(derived), None) => derived,
+        (None, _) => command.key().to_string(),
+    }
+}
+
+/// Matches `query` against the first of `texts` and, failing that, the best
+/// of the rest. A match of one of the rest scores half, so it ranks below an
+/// equally good match of the first, and has no matched characters to show.
+fn match_texts(texts: &[&str], query: &str, mode: MatchMode) -> Option<Match> {
+    let (first, rest) = texts.split_first()?;
+    match_query(first, query, mode).or_else(|| {
+        let best = rest
+            .iter()
+            .filter_map(|text| match_query(text, query, mode))
+            .map(|m| m.score)
+            .max()?;
+        Some(Match {
+            score: best / 2,
+            indices: Vec::new(),
+        })
+    })
+}
+
+/// The candidates the last query matched, so a query that only adds to it
+/// checks just those instead of every candidate. See [`filtered`].
+#[derive(Debug, Default)]
+pub struct FilterCache {
+    query: String,
+    mode: MatchMode,
+    /// How many candidates there were, as a guard against a changed list
+    candidates: usize,
+    /// Indices of every match, in candidate order. `None` until a query ran.
+    matched: Option<Vec<usize>>,
+}
+
+impl FilterCache {
+    /// Which candidates can still match `query`: only the previous matches
+    /// if `query` extends the previous query in substring mode, since a
+    /// longer substring only occurs where its prefix does. `None` if every
+    /// candidate has to be checked.
+    fn reusable(&self, query: &str, mode: MatchMode, candidates: usize) -> Option<&[usize]> {
+        let matched = self.matched.as_deref()?;
+        let reusable = mode == MatchMode::Substring
+            && self.mode == mode
+            && self.candidates == candidates
+            && query.starts_with(&self.query);
+        reusable.then_some(matched)
+    }
+}
+
+/// Matches `query` against each of `candidates`, best match first. Returns
+/// the index of each matching candidate with the byte offsets of its matched
+/// characters. Equally good matches are ordered by `frecency`, indexed like
+/// `candidates` with missing values counting as `0.0`, and keep their order
+/// after that. An empty query matches everything. At most `limit` matches are
+/// returned, all of them if it's `0`.
+///
+/// `cache` keeps the matches for the next call, which then only re-checks
+/// those when it can, see [`FilterCache`]. Pass the same cache for the same
+/// list of candidates, and a new one when the list changes.
+pub fn filtered<'a>(
+    candidates: impl IntoIterator<Item = &'a str>,
+    query: &str,
+    mode: MatchMode,
+    limit: usize,
+    frecency: &[f64],
+    cache: &mut FilterCache,
+) -> Vec<(usize, Vec<usize>)> {
+    let candidates: Vec<[&str; 1]> = candidates.into_iter().map(|c| [c]).collect();
+    filtered_fields(&candidates, query, mode, limit, frecency, cache)
+}
+
+/// Like [`filtered`], but each candidate has several texts to match, as given
+/// by [`search_texts`]. The first is the one shown and the only one whose
+/// matched characters are returned. The rest only let the candidate match,
+/// ranked below an equally good match of the first.
+pub fn filtered_fields<'a, T: AsRef<[&'a str]>>(
+    candidates: &[T],
+    query: &str,
+    mode: MatchMode,
+    limit: usize,
+    frecency: &[f64],
+    cache: &mut FilterCache,
+) -> Vec<(usize, Vec<usize>)> {
+    let check = |i: usize| Some((i, match_texts(candidates[i].as_ref(), query, mode)?));
+    let mut matches: Vec<_> = match cache.reusable(query, mode, candidates.len()) {
+        Some(previous) => previous.iter().filter_map(|&i| check(i)).collect(),
+        None => (0..candidates.len()).filter_map(check).collect(),
+    };
+    *cache = FilterCache {
+        query: query.to_string(),
+        mode,
+        candidates: candidates.len(),
+        matched: Some(matches.iter().map(|(i, _)| *i).collect()),
+    };
+    // Stable, so equally good and equally used matches keep their order
+    let frecency = |i: usize| frecency.get(i).copied().unwrap_or(0.0);
+    matches.sort_by(|(a, ma), (b, mb)| {
+        mb.score
+            .cmp(&ma.score)
+            .then_with(|| frecency(*b).total_cmp(&frecency(*a)))
+    });
+    if limit > 0 {
+        matches.truncate(limit);
+    }
+    matches.into_iter().map(|(i, m)| (i, m.indices)).collect()
+}
+
+/// The selection after moving `delta` rows from `index` in a list of `len`
+/// options, stopping at either end.
+pub fn move_selection(index: usize, delta: isize, len: usize) -> usize {
+    match len.checked_sub(1) {
+        Some(last) => index.saturating_add_signed(delta).min(last),
+        None => 0,
+    }
+}
+
+/// A row of the grouped list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListItem {
+    /// A category heading, drawn between the groups but never selected
+    Header(String),
+    /// The option at this index
+    Entry(usize),
+}
+
+/// The rows of the grouped list, each heading followed by the options in its
+/// range. Headings with nothing under them are left out.
+pub fn list_items(groups: &[(String, Range<usize>)]) -> Vec<ListItem> {
+    let mut items = Vec::new();
+    for (heading, range) in groups.iter().filter(|(_, range)| !range.is_empty()) {
+        items.push(ListItem::Header(heading.clone()));
+        items.extend(range.clone().map(ListItem::Entry));
+    }
+    items
+}
+
+/// Like [`move_selection`], but counts only the entries of `items`, so the
+/// selection steps over the headings between groups.
+pub fn move_selection_in(items: &[ListItem], index: usize, delta: isize) -> usize {
+    let entries: Vec<usize> = items
+        .iter()
+        .filter_map(|item| match item {
+            ListItem::Entry(i) => Some(*i),
+            ListItem::Header(_) => None,
+        })
+        .collect();
+    match entries.iter().position(|&i| i == index) {
+        Some(at) => entries[move_selection(at, delta, entries.len())],
+        None => entries.first().copied().unwrap_or(0),
+    }
+}
+
+/// The row, counting from the top, that the option at `index` is drawn on in
+/// a list of `len` options, drawn bottom-up with `reversed`. Also
+/// gives the option drawn on a row, since it's its own inverse.
+pub fn display_row(index: usize, len: usize, reversed: bool) -> usize {
+    if reversed {
+        len.saturating_sub(index + 1)
+    } else {
+        index
+    }
+}
+
+/// The option drawn `offset` rows below the first one in view, or `None` if
+/// fewer than `offset + 1` rows are in view.
+pub fn row_in_view(offset: usize, first_visible: usize, visible_rows: usize) -> Option<usize> {
+    (offset < visible_rows).then_some(first_visible + offset)
+}
+
+/// A readline-style edit of the query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEdit {
+    /// Delete everything before the cursor, like `Ctrl+U`
+    ClearLine,
+    /// Delete the word before the cursor and the spaces after it, like
+    /// `Ctrl+W`
+    DeleteWord,
+    /// Move the cursor to the start, like `Ctrl+A`
+    Start,
+    /// Move the cursor to the end, like `Ctrl+E`
+    End,
+}
+
+/// Applies `edit` to `text` with the cursor before the character at
+/// `cursor`, returning the new text and cursor. The cursor counts
+/// characters, as egui's does.
+pub fn edit_line(text: &str, cursor: usize, edit: LineEdit) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let delete_from = |start: usize| {
+        let edited = chars[..start].iter().chain(&chars[cursor..]).collect();
+        (edited, start)
+    };
+    match edit {
+        LineEdit::ClearLine => delete_from(0),
+        LineEdit::DeleteWord => {
+            let before = &chars[..cursor];
+            let word_end = before.iter().rposition(|c| !c.is_whitespace());
+            let start = word_end.map_or(0, |end| {
+                before[..end]
+                    .iter()
+                    .rposition(|c| c.is_whitespace())
+                    .map_or(0, |i| i + 1)
+            });
+            delete_from(start)
+        }
+        LineEdit::Start => (text.to_string(), 0),
+        LineEdit::End => (text.to_string(), chars.len()),
+    }
+}
+
+/// Height of a list of `rows` rows, each `row_height` tall and
+/// `row_padding` apart.
+pub fn list_height(rows: usize, row_height: f32, row_padding: f32) -> f32 {
+    match rows {
+        0 => 0.0,
+        rows => rows as f32 * (row_height + row_padding) - row_padding,
+    }
+}
+
+/// What the cancel key does
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cancel {
+    /// Empty the query and start over at the top of the list
+    ClearQuery,
+    /// Close the menu without selecting anything
+    Exit,
+}
+
+/// What the cancel key does with `query` typed. With `clears_first` a
+/// non-empty query is cleared before the next press closes the menu.
+pub fn cancel(query: &str, clears_first: bool) -> Cancel {
+    if clears_first && !query.is_empty() {
+        Cancel::ClearQuery
+    } else {
+        Cancel::Exit
+    }
+}
+
+/// The entries of `commands` that match `query`, best match first, after
+/// dropping duplicate keys like the menu does. An empty query keeps them all
+/// in order.
+pub fn select_from(commands: Vec<Command>, query: &str, mode: MatchMode) -> Vec<Command> {
+    let commands = dedup_commands(commands);
+    let displays = commands.iter().map(Command::display);
+    let order = filtered(displays, query, mode, 0, &[], &mut FilterCache::default());
+    let mut commands: Vec<Option<Command>> = commands.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|(i, _)| commands[i].take())
+        .collect()
+}
+
+/// One step of the scripted input for [`run_headless`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// Appends text to the query
+    Type(String),
+    /// Removes the last character of the query
+    Backspace,
+    /// Moves the selection up one row
+    Up,
+    /// Moves the selection down one row
+    Down,
+    /// Picks the selected entry
+    Accept,
+    /// Closes the menu without picking anything
+    Cancel,
+}
+
+/// Runs the menu over `commands` without a window, feeding it `inputs` in
+/// order. Returns the entry picked by [`Input::Accept`], or `None` if the
+/// menu was cancelled, nothing matched, or the input ran out first. Like the
+/// menu, editing the query moves the selection back to the top.
+pub fn run_headless(
+    commands: Vec<Command>,
+    mode: MatchMode,
+    inputs: impl IntoIterator<Item = Input>,
+) -> Option<Command> {
+    let commands = dedup_commands(commands);
+    let mut cache = FilterCache::default();
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut options = |query: &str| -> Vec<usize> {
+        let displays = commands.iter().map(Command::display);
+        filtered(displays, query, mode, 0, &[], &mut cache)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let mut shown = options(&query);
+    for input in inputs {
+        match input {
+            Input::Type(text) => query.push_str(&text),
+            Input::Backspace => {
+                query.pop();
+            }
+            Input::Up | Input::Down => {
+                let delta = if input == Input::Up { -1 } else { 1 };
+                selected = move_selection(selected, delta, shown.len());
+                continue;
+            }
+            Input::Accept => return shown.get(selected).map(|&i| commands[i].clone()),
+            Input::Cancel => return None,
+        }
+        selected = 0;
+        shown = options(&query);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop_entry::DesktopFile;
+    use crate::history::History;
+
+    const APPS: [&str; 4] = ["Firefox", "Files", "Terminal", "Text Editor"];
+
+    fn indices(query: &str, mode: MatchMode, limit: usize) -> Vec<usize> {
+        filtered(APPS, query, mode, limit, &[], &mut FilterCache::default())
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn empty_query_keeps_everything_in_order() {
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            assert_eq!(indices("", mode, 0), [0, 1, 2, 3]);
+            assert_eq!(indices("", mode, 2), [0, 1]);
+        }
+    }
+
+    #[test]
+    fn no_matches() {
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            assert!(indices("zzz", mode, 0).is_empty());
+        }
+    }
+
+    #[test]
+    fn matching_ignores_case_in_both_modes() {
+        assert_eq!(indices("FI", MatchMode::Substring, 0), [0, 1]);
+        assert_eq!(indices("tXe", MatchMode::Fuzzy, 0), [3]);
+        assert_eq!(indices("tXe", MatchMode::Substring, 0), Vec::<usize>::new());
+        assert_eq!(
+            filtered(
+                APPS,
+                "fire",
+                MatchMode::Substring,
+                0,
+                &[],
+                &mut FilterCache::default()
+            )[0]
+            .1,
+            [0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn frecency_breaks_ties_between_equal_matches() {
+        // "Files" and "Firefox" match "fi" equally well
+        let frecency = [0.0, 3.0];
+        let ranked: Vec<usize> = filtered(
+            APPS,
+            "fi",
+            MatchMode::Substring,
+            0,
+            &frecency,
+            &mut FilterCache::default(),
+        )
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect();
+        assert_eq!(ranked, [1, 0]);
+    }
+
+    #[test]
+    fn extra_fields_match_below_the_name() {
+        let firefox = Command::from_desktop_entry(
+            "firefox.desktop",
+            DesktopFile::parse(
+                "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox\n\
+                 GenericName=Web Browser\nKeywords=Internet;WWW;Browser;\n",
+            )
+            .unwrap()
+            .entry()
+            .unwrap(),
+            "C",
+        )
+        .unwrap();
+        let browser = Command::from("Browser Tools".to_string());
+        let matches = |fields: &[SearchField]| {
+            let texts = [
+                search_texts(&firefox, fields),
+                search_texts(&browser, fields),
+            ];
+            filtered_fields(
+                &texts,
+                "browser",
+                MatchMode::Substring,
+                0,
+                &[],
+                &mut FilterCache::default(),
+            )
+        };
+
+        assert_eq!(matches(&[]), [(1, vec![0, 1, 2, 3, 4, 5, 6])]);
+        // Only the keywords mention it, and no characters of "Firefox" light up
+        assert_eq!(
+            matches(&[SearchField::Keywords]),
+            [(1, vec![0, 1, 2, 3, 4, 5, 6]), (0, vec![])]
+        );
+        assert_eq!(matches(&[SearchField::GenericName]).len(), 2);
+    }
+
+    #[test]
+    fn sort_modes() {
+        let entries = [
+            Command::builder()
+                .key("term")
+                .display("terminal")
+                .with_category("System")
+                .build(),
+            Command::builder()
+                .key("ff")
+                .display("Firefox")
+                .with_category("Network")
+                .build(),
+            Command::builder().key("misc").display("Ark").build(),
+            Command::builder()
+                .key("calc")
+                .display("Calculator")
+                .with_category("Utility")
+                .build(),
+        ];
+        let frecency = [0.0, 2.0, 0.0, 5.0];
+        let order = |mode| sorted(&entries, &frecency, mode, 0);
+        assert_eq!(order(SortMode::Alphabetical), [2, 3, 1, 0]);
+        assert_eq!(order(SortMode::Frecency), [3, 1, 2, 0]);
+        // Accessories, Internet, System, then Other
+        assert_eq!(order(SortMode::CategoryThenName), [3, 1, 0, 2]);
+        assert_eq!(sorted(&entries, &[], SortMode::Frecency, 2), [2, 3]);
+    }
+
+    #[test]
+    fn grouped_lists_keep_the_loaded_order() {
+        let entries: Vec<Command> = ["Terminal", "Firefox", "Ark", "Calculator"]
+            .into_iter()
+            .map(|name| Command::new(name, name, name))
+            .collect();
+        let frecency = [0.0, 2.0, 0.0, 5.0];
+        // The grouped view's ranges index the entries as loaded, however used
+        let order = |limit| unfiltered(&entries, &frecency, SortMode::Frecency, limit, true);
+        assert_eq!(order(0), [0, 1, 2, 3]);
+        assert_eq!(order(2), [0, 1]);
+        assert_eq!(
+            unfiltered(&entries, &frecency, SortMode::Frecency, 0, false),
+            [3, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn cached_filtering_matches_a_full_scan() {
+        let apps = [
+            "Firefox",
+            "Files",
+            "Terminal",
+            "Text Editor",
+            "Fire Alarm",
+            "firewall-config",
+        ];
+        // Extending, then shrinking, replacing and extending again
+        let queries = [
+            "", "f", "fi", "fir", "fire", "firef", "fire", "fi", "te", "tex", "text ", "x", "",
+        ];
+        for mode in [MatchMode::Fuzzy, MatchMode::Substring] {
+            for limit in [0, 2] {
+                let mut cache = FilterCache::default();
+                for query in queries {
+                    assert_eq!(
+                        filtered(apps, query, mode, limit, &[], &mut cache),
+                        filtered(apps, query, mode, limit, &[], &mut FilterCache::default()),
+                        "{:?} in {:?} mode",
+                        query,
+                        mode
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_longer_query_only_rechecks_the_last_matches() {
+        let mut cache = FilterCache::default();
+        filtered(APPS, "te", MatchMode::Substring, 1, &[], &mut cache);
+        // Both matches are kept, not just the one returned
+        assert_eq!(
+            cache.reusable("tex", MatchMode::Substring, 4),
+            Some(&[2, 3][..])
+        );
+        assert_eq!(cache.reusable("t", MatchMode::Substring, 4), None);
+        assert_eq!(cache.reusable("tex", MatchMode::Fuzzy, 4), None);
+        assert_eq!(cache.reusable("tex", MatchMode::Substring, 5), None);
+    }
+
+    fn apps() -> Vec<Command> {
+        let mut apps: Vec<Command> = APPS.map(|app| Command::from(app.to_string())).into();
+        apps.push(Command::from("Files".to_string()));
+        apps
+    }
+
+    #[test]
+    fn select_from_dedups_and_ranks() {
+        let names = |commands: Vec<Command>| -> Vec<String> {
+            commands.into_iter().map(String::from).collect()
+        };
+        assert_eq!(
+            names(select_from(apps(), "", MatchMode::Fuzzy)),
+            APPS.map(String::from)
+        );
+        assert_eq!(
+            names(select_from(apps(), "te", MatchMode::Substring)),
+            ["Terminal", "Text Editor"]
+        );
+        assert!(select_from(apps(), "zzz", MatchMode::Fuzzy).is_empty());
+    }
+
+    #[test]
+    fn headless_runs_follow_the_script() {
+        use Input::*;
+        let run = |inputs: Vec<Input>| {
+            run_headless(apps(), MatchMode::Substring, inputs).map(String::from)
+        };
+        assert_eq!(run(vec![Accept]).as_deref(), Some("Firefox"));
+        assert_eq!(
+            run(vec![Type("te".into()), Down, Down, Accept]).as_deref(),
+            Some("Text Editor")
+        );
+        // Editing the query puts the selection back at the top
+        assert_eq!(
+            run(vec![Down, Down, Type("fi".into()), Backspace, Accept]).as_deref(),
+            Some("Firefox")
+        );
+        assert_eq!(run(vec![Type("zzz".into()), Accept]), None);
+        assert_eq!(run(vec![Type("fi".into()), Cancel, Accept]), None);
+        assert_eq!(run(vec![Type("fi".into())]), None);
+    }
+
+    #[test]
+    fn short_queries_are_counted_in_characters() {
+        assert!(!too_short("", 3));
+        assert!(too_short("f", 3));
+        assert!(too_short("fi", 3));
+        assert!(!too_short("fir", 3));
+        assert!(too_short("éè", 3));
+        assert!(!too_short("f", 0));
+    }
+
+    #[test]
+    fn lone_matches_wait_for_the_query_to_settle() {
+        assert_eq!(settle_time(1.0, 1.0, 500), Duration::from_millis(500));
+        assert_eq!(settle_time(1.0, 1.25, 500), Duration::from_millis(250));
+        assert_eq!(settle_time(1.0, 1.5, 500), Duration::ZERO);
+        assert_eq!(settle_time(1.0, 9.0, 500), Duration::ZERO);
+        // Typing again starts the wait over
+        assert_eq!(settle_time(1.4, 1.5, 500), Duration::from_millis(400));
+        assert_eq!(settle_time(2.0, 1.0, 500), Duration::from_millis(500));
+        assert_eq!(settle_time(1.0, 1.0, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn line_edits_work_like_readline() {
+        use LineEdit::*;
+        assert_eq!(edit_line("fire fox", 8, ClearLine), (String::new(), 0));
+        assert_eq!(edit_line("fire fox", 5, ClearLine), ("fox".to_string(), 0));
+
+        assert_eq!(
+            edit_line("fire fox", 8, DeleteWord),
+            ("fire ".to_string(), 5)
+        );
+        assert_eq!(
+            edit_line("fire fox  ", 10, DeleteWord),
+            ("fire ".to_string(), 5)
+        );
+        assert_eq!(
+            edit_line("fire fox", 6, DeleteWord),
+            ("fire ox".to_string(), 5)
+        );
+        assert_eq!(edit_line("fire", 4, DeleteWord), (String::new(), 0));
+        assert_eq!(edit_line("   ", 3, DeleteWord), (String::new(), 0));
+        assert_eq!(edit_line("", 0, DeleteWord), (String::new(), 0));
+        assert_eq!(
+            edit_line("größe öl", 8, DeleteWord),
+            ("größe ".to_string(), 6)
+        );
+
+        assert_eq!(edit_line("fire", 2, Start), ("fire".to_string(), 0));
+        assert_eq!(edit_line("größe", 1, End), ("größe".to_string(), 5));
+    }
+
+    #[test]
+    fn selection_stops_at_both_ends() {
+        assert_eq!(move_selection(0, -1, 4), 0);
+        assert_eq!(move_selection(1, -5, 4), 0);
+        assert_eq!(move_selection(3, 1, 4), 3);
+        assert_eq!(move_selection(2, 10, 4), 3);
+        assert_eq!(move_selection(1, 1, 4), 2);
+        assert_eq!(move_selection(0, 1, 0), 0);
+    }
+
+    #[test]
+    fn frecency_keys_survive_renamed_desktop_files() {
+        let old = Command::builder()
+            .key("org.example.Editor.desktop")
+            .command("/usr/bin/editor --new-window")
+            .build();
+        let renamed = Command::builder()
+            .key("editor.desktop")
+            .command("editor")
+            .build();
+        let action = Command::builder()
+            .key("editor.desktop::new-file")
+            .command("editor --new-file")
+            .build();
+
+        assert_ne!(
+            frecency_key(&old, FrecencyKey::Id),
+            frecency_key(&renamed, FrecencyKey::Id)
+        );
+        assert_eq!(frecency_key(&old, FrecencyKey::Binary), "editor");
+        assert_eq!(frecency_key(&renamed, FrecencyKey::Binary), "editor");
+        assert_eq!(
+            frecency_key(&action, FrecencyKey::Binary),
+            "editor::new-file"
+        );
+        // Nothing to go on, so the key it is
+        assert_eq!(
+            frecency_key(&old, FrecencyKey::WmClass),
+            "org.example.Editor.desktop"
+        );
+
+        let mut history = History::default();
+        history.record(&frecency_key(&old, FrecencyKey::Binary), 1000);
+        assert!(history.frecency(&frecency_key(&renamed, FrecencyKey::Binary), 1000) > 0.0);
+        assert_eq!(
+            history.frecency(&frecency_key(&renamed, FrecencyKey::Id), 1000),
+            0.0
+        );
+    }
+
+    #[test]
+    fn grouped_selection_skips_headers() {
+        let groups = vec![
+            ("Internet".to_string(), 0..2),
+            ("Empty".to_string(), 2..2),
+            ("Office".to_string(), 2..4),
+        ];
+        let items = list_items(&groups);
+        assert_eq!(
+            items,
+            vec![
+                ListItem::Header("Internet".to_string()),
+                ListItem::Entry(0),
+                ListItem::Entry(1),
+                ListItem::Header("Office".to_string()),
+                ListItem::Entry(2),
+                ListItem::Entry(3),
+            ]
+        );
+        // From the last entry of one group to the first of the next
+        assert_eq!(move_selection_in(&items, 1, 1), 2);
+        assert_eq!(move_selection_in(&items, 2, -1), 1);
+        assert_eq!(move_selection_in(&items, 0, -1), 0);
+        assert_eq!(move_selection_in(&items, 3, 5), 3);
+        assert_eq!(move_selection_in(&[], 0, 1), 0);
+    }
+
+    #[test]
+    fn reversed_lists_draw_the_best_match_last() {
+        assert_eq!(display_row(0, 4, false), 0);
+        assert_eq!(display_row(0, 4, true), 3);
+        assert_eq!(display_row(3, 4, true), 0);
+        for i in 0..4 {
+            assert_eq!(display_row(display_row(i, 4, true), 4, true), i);
+        }
+        // Up on screen, one row nearer the top, is the next worse match
+        let up = |index| display_row(display_row(index, 4, true) - 1, 4, true);
+        assert_eq!(up(0), 1);
+        assert_eq!(up(2), 3);
+    }
+
+    #[test]
+    fn digit_hotkeys_only_reach_rows_in_view() {
+        assert_eq!(row_in_view(0, 5, 3), Some(5));
+        assert_eq!(row_in_view(2, 5, 3), Some(7));
+        assert_eq!(row_in_view(3, 5, 3), None);
+        assert_eq!(row_in_view(0, 0, 0), None);
+    }
+
+    #[test]
+    fn list_height_leaves_out_the_last_gap() {
+        assert_eq!(list_height(0, 20.0, 3.0), 0.0);
+        assert_eq!(list_height(1, 20.0, 3.0), 20.0);
+        assert_eq!(list_height(3, 20.0, 3.0), 66.0);
+    }
+
+    #[test]
+    fn cancel_clears_the_query_before_exiting() {
+        assert_eq!(cancel("fire", false), Cancel::Exit);
+        assert_eq!(cancel("", false), Cancel::Exit);
+
+        assert_eq!(cancel("fire", true), Cancel::ClearQuery);
+        // The query is empty after clearing, so the next press exits
+        assert_eq!(cancel("", true), Cancel::Exit);
+    }
+}
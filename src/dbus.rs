@@ -0,0 +1,86 @@
+//! Starting `DBusActivatable` applications through the
+//! `org.freedesktop.Application` interface, as the desktop entry spec asks.
+
+/// The interface every D-Bus activatable application implements
+pub const INTERFACE: &str = "org.freedesktop.Application";
+
+/// The well-known bus name of the application with desktop file ID `id`,
+/// which is the ID without `.desktop`. `None` if that isn't a valid bus name,
+/// e.g. `firefox.desktop`, whose name has a single element.
+pub fn bus_name(id: &str) -> Option<&str> {
+    let name = id.strip_suffix(".desktop")?;
+    let valid_element = |element: &str| {
+        element.chars().next().is_some_and(|c| !c.is_ascii_digit())
+            && element
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    let valid = name.len() <= 255 && name.contains('.') && name.split('.').all(valid_element);
+    valid.then_some(name)
+}
+
+/// The object path an application exports its interface at: the bus name with
+/// `.` turned into `/` and `-` into `_`.
+pub fn object_path(bus_name: &str) -> String {
+    format!("/{}", bus_name.replace('.', "/").replace('-', "_"))
+}
+
+/// Calls `Activate`, or `ActivateAction` for `action`, on the application
+/// owning `bus_name` on the session bus.
+#[cfg(feature = "dbus")]
+pub fn activate(bus_name: &str, action: Option<&str>) -> zbus::Result<()> {
+    use std::collections::HashMap;
+    use zbus::zvariant::Value;
+
+    let connection = zbus::blocking::Connection::session()?;
+    let path = object_path(bus_name);
+    let platform_data: HashMap<&str, Value> = HashMap::new();
+    match action {
+        None => connection.call_method(
+            Some(bus_name),
+            path.as_str(),
+            Some(INTERFACE),
+            "Activate",
+            &(platform_data,),
+        )?,
+        Some(action) => connection.call_method(
+            Some(bus_name),
+            path.as_str(),
+            Some(INTERFACE),
+            "ActivateAction",
+            &(action, Vec::<Value>::new(), platform_data),
+        )?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_bus_names_from_desktop_file_ids() {
+        assert_eq!(
+            bus_name("org.gnome.Nautilus.desktop"),
+            Some("org.gnome.Nautilus")
+        );
+        assert_eq!(
+            bus_name("org.gnome.font-viewer.desktop"),
+            Some("org.gnome.font-viewer")
+        );
+        assert_eq!(bus_name("firefox.desktop"), None);
+        assert_eq!(bus_name("org.gnome.Nautilus"), None);
+        assert_eq!(bus_name("org.7zip.App.desktop"), None);
+        assert_eq!(bus_name("org..App.desktop"), None);
+        assert_eq!(bus_name("org.example.my app.desktop"), None);
+    }
+
+    #[test]
+    fn derives_object_paths_from_bus_names() {
+        assert_eq!(object_path("org.gnome.Nautilus"), "/org/gnome/Nautilus");
+        assert_eq!(
+            object_path("org.gnome.font-viewer"),
+            "/org/gnome/font_viewer"
+        );
+    }
+}
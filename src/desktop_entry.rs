@@ -1,11 +1,15 @@
 // src/desktop.rs
 
-use serde::de::{self, Deserializer};
-use serde::ser::Serializer;
+use crate::scanner;
+use serde::de::{self, Deserializer, IntoDeserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
 use serde::{Deserialize, Serialize};
-use serde_aux::prelude::deserialize_boolean_from_string;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 /// Represents a semicolon‐separated list (e.g. "AudioVideo;Video;Player;")
 /// and always serializes with a trailing semicolon if non‐empty.
@@ -18,16 +22,13 @@ impl<'de> Deserialize<'de> for SemicolonList {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        // Split on ';', drop empty trailing, but keep empty items if interior.
+        // Split on ';' and drop empty items. Items are trimmed, since
+        // `Categories=Network; WebBrowser;` is common enough.
         let items: Vec<String> = s
             .split(';')
-            .filter_map(|part| {
-                if part.is_empty() {
-                    None
-                } else {
-                    Some(part.to_string())
-                }
-            })
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
             .collect();
         Ok(SemicolonList(items))
     }
@@ -38,16 +39,18 @@ impl Serialize for SemicolonList {
     where
         S: Serializer,
     {
-        if self.0.is_empty() {
-            serializer.serialize_str("") // empty string
-        } else {
-            let mut joined = self.0.join(";");
-            // ensure trailing semicolon
-            if !joined.ends_with(';') {
-                joined.push(';');
-            }
-            serializer.serialize_str(&joined)
+        serializer.collect_str(self)
+    }
+}
+
+/// Writes the items each followed by `;`, as the spec asks, or nothing for
+/// an empty list
+impl fmt::Display for SemicolonList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.0 {
+            write!(f, "{};", item)?;
         }
+        Ok(())
     }
 }
 
@@ -62,8 +65,93 @@ impl Serialize for SemicolonList {
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct LocaleMap(pub BTreeMap<String, String>);
 
+/// Splits a `lang_COUNTRY.ENCODING@MODIFIER` locale into its language,
+/// country and modifier, dropping the encoding. The language is lowercased
+/// and the country uppercased.
+fn locale_parts(locale: &str) -> (String, Option<String>, Option<&str>) {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let rest = rest.split('.').next().unwrap_or_default();
+    let (lang, country) = match rest.split_once('_') {
+        Some((lang, country)) => (lang, Some(country.to_ascii_uppercase())),
+        None => (rest, None),
+    };
+    (lang.to_ascii_lowercase(), country, modifier)
+}
+
+/// Writes `locale` the canonical way, `lang_COUNTRY@MODIFIER` with a lowercase
+/// language and an uppercase country, so `pt_br` and `pt_BR` are the same
+/// locale. The encoding is dropped, since desktop files don't use it.
+///
+/// ```
+/// use rmenu_ng::desktop_entry::normalize_locale;
+///
+/// assert_eq!(normalize_locale("pt_br"), "pt_BR");
+/// assert_eq!(normalize_locale("SR_rs.UTF-8@latin"), "sr_RS@latin");
+/// assert_eq!(normalize_locale(""), "");
+/// ```
+pub fn normalize_locale(locale: &str) -> String {
+    let (mut normalized, country, modifier) = locale_parts(locale);
+    if let Some(country) = country {
+        normalized.push('_');
+        normalized.push_str(&country);
+    }
+    if let Some(modifier) = modifier {
+        normalized.push('@');
+        normalized.push_str(modifier);
+    }
+    normalized
+}
+
+impl LocaleMap {
+    /// Looks up the value for a `lang_COUNTRY.ENCODING@MODIFIER` locale, following
+    /// the spec's fallback order: `lang_COUNTRY@MODIFIER`, `lang_COUNTRY`,
+    /// `lang@MODIFIER`, `lang`, then the un-localized default. Language and
+    /// country are matched ignoring case, see [`normalize_locale`].
+    pub fn get_for_locale(&self, locale: &str) -> Option<&str> {
+        let (lang, country, modifier) = locale_parts(locale);
+
+        let mut candidates = Vec::with_capacity(5);
+        if let (Some(country), Some(modifier)) = (&country, modifier) {
+            candidates.push(format!("{}_{}@{}", lang, country, modifier));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{}_{}", lang, country));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{}@{}", lang, modifier));
+        }
+        candidates.push(lang);
+        candidates.push(String::new());
+
+        candidates
+            .iter()
+            .find_map(|key| self.0.get(key))
+            .map(String::as_str)
+    }
+
+    /// Sets the value for exactly `locale`, `""` being the un-localized
+    /// default. The locale is stored normalized, see [`normalize_locale`].
+    pub fn set(&mut self, locale: &str, value: impl Into<String>) {
+        self.0.insert(normalize_locale(locale), value.into());
+    }
+}
+
+/// The locale used for messages, as set by `$LC_ALL`, `$LC_MESSAGES` or `$LANG`.
+/// Returns an empty string (the un-localized default) when none is set.
+pub fn current_locale() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| value != "C" && value != "POSIX")
+        .unwrap_or_default()
+}
+
 impl<'de> Deserialize<'de> for LocaleMap {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -84,7 +172,7 @@ impl<'de> Deserialize<'de> for LocaleMap {
 }
 
 impl Serialize for LocaleMap {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
@@ -100,43 +188,41 @@ impl Serialize for LocaleMap {
     }
 }
 
-/// Pull out all keys matching `prefix` or `prefix[<locale>]` from a flatten map.
-fn deserialize_localized<'de, D>(
-    prefix: &str,
-    map: &mut BTreeMap<String, String>,
-) -> Option<LocaleMap> {
-    // collect any entry whose key == prefix or key starts with prefix + "[".
-    let mut loc_map = LocaleMap(BTreeMap::new());
-    let mut to_remove = Vec::new();
-
-    for key in map.keys() {
-        if key == prefix {
-            // default
-            to_remove.push(key.clone());
-        } else if key.starts_with(&format!("{}[", prefix)) && key.ends_with(']') {
-            to_remove.push(key.clone());
-        }
+/// The locale of a `prefix[<locale>]` key, `""` for `prefix` itself, or
+/// `None` if `key` isn't one of the two. The locale must be non-empty and the
+/// brackets balanced, so keys like `Name[]` or `Name[de]extra` don't count.
+fn locale_of<'a>(prefix: &str, key: &'a str) -> Option<&'a str> {
+    let rest = key.strip_prefix(prefix)?;
+    if rest.is_empty() {
+        return Some("");
     }
-    if to_remove.is_empty() {
+    let locale = rest.strip_prefix('[')?.strip_suffix(']')?;
+    let valid = !locale.is_empty() && !locale.contains(['[', ']']);
+    valid.then_some(locale)
+}
+
+/// Pull out all keys matching `prefix` or `prefix[<locale>]` from a flatten
+/// map. Malformed variants are left in the map, so they end up in `other`.
+fn deserialize_localized(prefix: &str, map: &mut BTreeMap<String, String>) -> Option<LocaleMap> {
+    let keys: Vec<(String, String)> = map
+        .keys()
+        .filter_map(|key| Some((key.clone(), locale_of(prefix, key)?.to_string())))
+        .collect();
+    if keys.is_empty() {
         return None;
     }
-    for full_key in to_remove {
-        if let Some(value) = map.remove(&full_key) {
-            if full_key == prefix {
-                loc_map.0.insert("".into(), value);
-            } else if let Some(start) = full_key.find('[') {
-                // e.g. Name[de] => locale = "de"
-                let locale = full_key[start + 1..full_key.len() - 1].to_string();
-                loc_map.0.insert(locale, value);
-            }
+    let mut loc_map = LocaleMap(BTreeMap::new());
+    for (key, locale) in keys {
+        if let Some(value) = map.remove(&key) {
+            // `Name[pt_BR]` sorts before `Name[pt_br]`, so it wins if both are there
+            loc_map.0.entry(normalize_locale(&locale)).or_insert(value);
         }
     }
     Some(loc_map)
 }
 
 /// The `[Desktop Entry]` section.  Corresponds to "Table 2. Standard Keys".
-#[derive(Debug, Serialize, Deserialize, Default)]
-#[serde(deny_unknown_fields)]
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct DesktopEntry {
     /// Type=Application | Link | Directory
     #[serde(rename = "Type")]
@@ -159,7 +245,7 @@ pub struct DesktopEntry {
     #[serde(
         rename = "NoDisplay",
         default,
-        deserialize_with = "deserialize_boolean_from_string"
+        deserialize_with = "deserialize_opt_bool"
     )]
     pub no_display: Option<bool>,
 
@@ -172,11 +258,7 @@ pub struct DesktopEntry {
     pub icon: Option<LocaleMap>,
 
     /// Hidden=true/false
-    #[serde(
-        rename = "Hidden",
-        default,
-        deserialize_with = "deserialize_boolean_from_string"
-    )]
+    #[serde(rename = "Hidden", default, deserialize_with = "deserialize_opt_bool")]
     pub hidden: Option<bool>,
 
     /// OnlyShowIn=… (semicolon list)
@@ -199,7 +281,7 @@ pub struct DesktopEntry {
     #[serde(
         rename = "DBusActivatable",
         default,
-        deserialize_with = "deserialize_boolean_from_string"
+        deserialize_with = "deserialize_opt_bool"
     )]
     pub dbus_activatable: Option<bool>,
 
@@ -219,7 +301,7 @@ pub struct DesktopEntry {
     #[serde(
         rename = "Terminal",
         default,
-        deserialize_with = "deserialize_boolean_from_string"
+        deserialize_with = "deserialize_opt_bool"
     )]
     pub terminal: Option<bool>,
 
@@ -263,7 +345,7 @@ pub struct DesktopEntry {
     #[serde(
         rename = "StartupNotify",
         default,
-        deserialize_with = "deserialize_boolean_from_string"
+        deserialize_with = "deserialize_opt_bool"
     )]
     pub startup_notify: Option<bool>,
 
@@ -279,7 +361,7 @@ pub struct DesktopEntry {
     #[serde(
         rename = "PrefersNonDefaultGPU",
         default,
-        deserialize_with = "deserialize_boolean_from_string"
+        deserialize_with = "deserialize_opt_bool"
     )]
     pub prefers_non_default_gpu: Option<bool>,
 
@@ -288,9 +370,184 @@ pub struct DesktopEntry {
     pub other: BTreeMap<String, String>,
 }
 
+impl DesktopEntry {
+    /// The `Name` for `locale`, with the fallbacks of
+    /// [`LocaleMap::get_for_locale`]. Empty if the entry has none.
+    ///
+    /// ```
+    /// use rmenu_ng::desktop_entry::DesktopEntry;
+    ///
+    /// let mut entry = DesktopEntry::default();
+    /// entry.set_name("", "Files");
+    /// entry.set_name("de", "Dateien");
+    /// assert_eq!(entry.name("de_AT.UTF-8"), "Dateien");
+    /// assert_eq!(entry.name("fr"), "Files");
+    /// ```
+    pub fn name(&self, locale: &str) -> &str {
+        self.name.get_for_locale(locale).unwrap_or_default()
+    }
+
+    /// Sets the `Name` for exactly `locale`, `""` being `Name` itself
+    pub fn set_name(&mut self, locale: &str, value: impl Into<String>) {
+        self.name.set(locale, value);
+    }
+
+    /// The `GenericName` for `locale`, like [`DesktopEntry::name`]
+    pub fn generic_name(&self, locale: &str) -> Option<&str> {
+        self.generic_name.as_ref()?.get_for_locale(locale)
+    }
+
+    /// Sets the `GenericName` for exactly `locale`
+    pub fn set_generic_name(&mut self, locale: &str, value: impl Into<String>) {
+        self.generic_name.get_or_insert_default().set(locale, value);
+    }
+
+    /// The `Comment` for `locale`, like [`DesktopEntry::name`]
+    pub fn comment(&self, locale: &str) -> Option<&str> {
+        self.comment.as_ref()?.get_for_locale(locale)
+    }
+
+    /// Sets the `Comment` for exactly `locale`
+    pub fn set_comment(&mut self, locale: &str, value: impl Into<String>) {
+        self.comment.get_or_insert_default().set(locale, value);
+    }
+
+    /// The `Keywords` for `locale`, split into single keywords. Empty if
+    /// there are none.
+    ///
+    /// ```
+    /// use rmenu_ng::desktop_entry::DesktopEntry;
+    ///
+    /// let mut entry = DesktopEntry::default();
+    /// entry.set_keywords("", ["browser", "web"]);
+    /// assert_eq!(entry.keywords("en_US"), ["browser", "web"]);
+    /// assert_eq!(entry.keywords.unwrap().0[""], "browser;web;");
+    /// ```
+    pub fn keywords(&self, locale: &str) -> Vec<&str> {
+        let keywords = self
+            .keywords
+            .as_ref()
+            .and_then(|k| k.get_for_locale(locale));
+        keywords
+            .unwrap_or_default()
+            .split(';')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .collect()
+    }
+
+    /// Sets the `Keywords` for exactly `locale`, written as a list with each
+    /// keyword followed by `;`
+    pub fn set_keywords<I>(&mut self, locale: &str, keywords: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let list = SemicolonList(keywords.into_iter().map(Into::into).collect());
+        self.keywords
+            .get_or_insert_default()
+            .set(locale, list.to_string());
+    }
+
+    /// Whether the entry declares it opens `MimeType` `mime`
+    pub fn handles_mime(&self, mime: &str) -> bool {
+        self.mime_type
+            .as_ref()
+            .is_some_and(|types| types.0.iter().any(|m| m == mime))
+    }
+
+    /// Whether the entry declares it opens URLs with `scheme`, either as an
+    /// `x-scheme-handler/<scheme>` `MimeType` or in KDE's `X-KDE-Protocols`.
+    /// Schemes are compared ignoring case.
+    ///
+    /// ```
+    /// use rmenu_ng::desktop_entry::parse_desktop_file;
+    ///
+    /// let file = parse_desktop_file(
+    ///     "[Desktop Entry]\nType=Application\nName=Mail\n\
+    ///      MimeType=x-scheme-handler/mailto;\nX-KDE-Protocols=imap,imaps\n",
+    /// )
+    /// .unwrap();
+    /// let entry = file.entry().unwrap();
+    /// assert!(entry.handles_scheme("MAILTO"));
+    /// assert!(entry.handles_scheme("imaps"));
+    /// assert!(!entry.handles_scheme("https"));
+    /// ```
+    pub fn handles_scheme(&self, scheme: &str) -> bool {
+        let mime_types = self.mime_type.as_ref().map_or(&[][..], |m| &m.0[..]);
+        let by_mime = mime_types.iter().any(|m| {
+            m.strip_prefix("x-scheme-handler/")
+                .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+        });
+        // KDE writes a comma-separated list, but semicolons turn up too
+        let by_protocols = self.other.get("X-KDE-Protocols").is_some_and(|protocols| {
+            protocols
+                .split([',', ';'])
+                .any(|p| p.trim().eq_ignore_ascii_case(scheme))
+        });
+        by_mime || by_protocols
+    }
+
+    /// Whether the entry belongs in a menu, honoring `Hidden`, `NoDisplay`,
+    /// `OnlyShowIn` and `NotShowIn` for the given `$XDG_CURRENT_DESKTOP` values.
+    ///
+    /// With no current desktop, e.g. under a bare window manager, nothing
+    /// matches: `OnlyShowIn` entries are hidden unless
+    /// `only_show_in_when_unknown` is set, and `NotShowIn` hides nothing.
+    pub fn should_show(
+        &self,
+        current_desktops: &[String],
+        only_show_in_when_unknown: bool,
+    ) -> bool {
+        self.hidden_by(current_desktops, only_show_in_when_unknown)
+            .is_none()
+    }
+
+    /// The key that keeps the entry out of the menu, if any, as decided by
+    /// [`DesktopEntry::should_show`].
+    pub fn hidden_by(
+        &self,
+        current_desktops: &[String],
+        only_show_in_when_unknown: bool,
+    ) -> Option<&'static str> {
+        if self.hidden == Some(true) {
+            return Some("Hidden");
+        }
+        if self.no_display == Some(true) {
+            return Some("NoDisplay");
+        }
+        let unknown = current_desktops.is_empty();
+        if let Some(only) = &self.only_show_in
+            && !(unknown && only_show_in_when_unknown)
+            && !only.0.iter().any(|d| current_desktops.contains(d))
+        {
+            return Some("OnlyShowIn");
+        }
+        if let Some(not) = &self.not_show_in
+            && not.0.iter().any(|d| current_desktops.contains(d))
+        {
+            return Some("NotShowIn");
+        }
+        None
+    }
+}
+
+/// The desktops named in the colon-separated `$XDG_CURRENT_DESKTOP`. Empty
+/// when it's unset or blank.
+pub fn current_desktops() -> Vec<String> {
+    parse_desktops(&env::var("XDG_CURRENT_DESKTOP").unwrap_or_default())
+}
+
+fn parse_desktops(value: &str) -> Vec<String> {
+    value
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 /// The `[Desktop Action <ActionID>]` section.  Corresponds to Table 3.
-#[derive(Debug, Serialize, Deserialize, Default)]
-#[serde(deny_unknown_fields)]
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct DesktopAction {
     /// Name=…  (localized)
     #[serde(skip)]
@@ -308,29 +565,23 @@ pub struct DesktopAction {
     pub other: BTreeMap<String, String>,
 }
 
-/// A single INI‐style section. We attempt to parse "Desktop Entry" into `Section::Entry`,
+/// A single INI‐style section. We parse "Desktop Entry" into `Section::Entry`,
 /// "Desktop Action <ID>" into `Section::Action { id, data }`, and anything else into
 /// `Section::Other`, which just stores a flatten‐map of keys/values unchanged.
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
+/// The section name decides the variant; see `DesktopFile`'s `Deserialize` impl.
+#[derive(Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)] // a file has a single `Entry`
 pub enum Section {
     Entry {
-        #[serde(rename = "Desktop Entry")]
-        #[serde(deserialize_with = "deserialize_desktop_entry")]
-        #[serde(serialize_with = "serialize_desktop_entry")]
-        pub desktop_entry: DesktopEntry,
+        desktop_entry: DesktopEntry,
     },
 
     Action {
-        #[serde(rename = "Desktop Action")]
-        #[serde(deserialize_with = "deserialize_desktop_action")]
-        #[serde(serialize_with = "serialize_desktop_action")]
-        pub action: (String /*action_id*/, DesktopAction),
+        action: (String /*action_id*/, DesktopAction),
     },
 
     Other {
-        #[serde(flatten)]
-        pub raw: BTreeMap<String, String>,
+        raw: BTreeMap<String, String>,
     },
 }
 
@@ -339,10 +590,321 @@ pub enum Section {
 ///   "Desktop Entry"               => Section::Entry
 ///   "Desktop Action Gallery"      => Section::Action("Gallery", DesktopAction)
 ///   "X-KDE-SomeGroup"             => Section::Other { … }
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct DesktopFile {
-    #[serde(flatten)]
+    /// The desktop file ID (e.g. `firefox.desktop`). Filled in by the scanner,
+    /// empty when parsed from a string.
+    pub id: String,
     pub sections: BTreeMap<String, Section>,
+    /// Filled in by [`DesktopFile::parse`]; serde doesn't see comments
+    pub comments: Comments,
+}
+
+/// The comment and blank lines of a desktop file, which the INI parser drops.
+/// Each run of them is kept with the line it came before, so writing the file
+/// back puts it in front of that same header or key even though sections and
+/// keys come out reordered.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Comments {
+    /// Lines before the first section header
+    pub header: Vec<String>,
+    /// Lines before a `[section]` header other than the first, by section name
+    pub before_section: BTreeMap<String, Vec<String>>,
+    /// Lines before a key, by section name and key
+    pub before_key: BTreeMap<(String, String), Vec<String>>,
+    /// Lines after the last key
+    pub trailing: Vec<String>,
+}
+
+impl Comments {
+    /// Collects the comments of `contents`. Lines that don't parse are
+    /// skipped; parsing the file itself reports them.
+    fn collect(contents: &str) -> Comments {
+        let mut comments = Comments::default();
+        let mut pending = Vec::new();
+        let mut section: Option<String> = None;
+        for item in serde_ini::Parser::from_str(contents).flatten() {
+            match item {
+                serde_ini::Item::Comment { text } => pending.push(text),
+                serde_ini::Item::Empty => pending.push(String::new()),
+                serde_ini::Item::Section { name } => {
+                    let lines = std::mem::take(&mut pending);
+                    if section.is_none() {
+                        comments.header = lines;
+                    } else if !lines.is_empty() {
+                        comments
+                            .before_section
+                            .entry(name.clone())
+                            .or_default()
+                            .extend(lines);
+                    }
+                    section = Some(name);
+                }
+                serde_ini::Item::Value { key, .. } => {
+                    if !pending.is_empty() {
+                        let section = section.clone().unwrap_or_default();
+                        let lines = std::mem::take(&mut pending);
+                        comments
+                            .before_key
+                            .entry((section, key))
+                            .or_default()
+                            .extend(lines);
+                    }
+                }
+            }
+        }
+        comments.trailing = pending;
+        comments
+    }
+}
+
+impl DesktopFile {
+    /// Returns the `[Desktop Entry]` section, if the file has one.
+    pub fn entry(&self) -> Option<&DesktopEntry> {
+        match self.sections.get("Desktop Entry") {
+            Some(Section::Entry { desktop_entry }) => Some(desktop_entry),
+            _ => None,
+        }
+    }
+
+    /// Parses a desktop file, keeping its comments and blank lines.
+    pub fn parse(contents: &str) -> Result<DesktopFile, serde_ini::de::Error> {
+        let mut file: DesktopFile = serde_ini::from_str(contents)?;
+        file.comments = Comments::collect(contents);
+        Ok(file)
+    }
+
+    /// Like [`DesktopFile::parse`], but fails on a group header that appears
+    /// twice, which the spec forbids. `parse` keeps only the last such group.
+    pub fn parse_strict(contents: &str) -> Result<DesktopFile, serde_ini::de::Error> {
+        let mut seen = HashSet::new();
+        for item in serde_ini::Parser::from_str(contents).flatten() {
+            if let serde_ini::Item::Section { name } = item
+                && !seen.insert(name.clone())
+            {
+                return Err(de::Error::custom(format!("duplicate group [{}]", name)));
+            }
+        }
+        DesktopFile::parse(contents)
+    }
+
+    /// Writes the file out with `\n` line endings, with the lines in
+    /// [`DesktopFile::comments`] back in front of the headers and keys they
+    /// preceded. `[Desktop Entry]` comes first, then the other sections in
+    /// sorted order. Known keys come in the spec's order, `Type`, `Version`,
+    /// `Name` and so on, followed by the rest in sorted order.
+    pub fn to_ini_string(&self) -> Result<String, serde_ini::ser::Error> {
+        let mut ini = Vec::new();
+        let writer = serde_ini::Writer::new(&mut ini, serde_ini::LineEnding::Linefeed);
+        self.serialize(&mut serde_ini::Serializer::new(writer))?;
+        let ini = String::from_utf8(ini).expect("serde_ini writes UTF-8");
+
+        let mut out = String::with_capacity(ini.len());
+        let push_lines = |out: &mut String, lines: Option<&Vec<String>>| {
+            for line in lines.into_iter().flatten() {
+                out.push_str(line);
+                out.push('\n');
+            }
+        };
+        push_lines(&mut out, Some(&self.comments.header));
+        let mut section = String::new();
+        for line in ini.lines() {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.to_string();
+                push_lines(&mut out, self.comments.before_section.get(name));
+            } else if let Some((key, _)) = line.split_once('=') {
+                let lines = self
+                    .comments
+                    .before_key
+                    .get(&(section.clone(), key.to_string()));
+                push_lines(&mut out, lines);
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        push_lines(&mut out, Some(&self.comments.trailing));
+        Ok(out)
+    }
+
+    /// Returns the `[Desktop Action <id>]` section, if the file has one.
+    pub fn action(&self, id: &str) -> Option<&DesktopAction> {
+        match self.sections.get(&format!("Desktop Action {}", id)) {
+            Some(Section::Action {
+                action: (_, action),
+            }) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// Every `[Desktop Action <id>]` section as `(id, action)`, sorted by ID.
+    /// Unlike the entry's `Actions` key, this includes actions it doesn't
+    /// list.
+    pub fn actions(&self) -> impl Iterator<Item = (&str, &DesktopAction)> {
+        self.sections.values().filter_map(|section| match section {
+            Section::Action {
+                action: (id, action),
+            } => Some((id.as_str(), action)),
+            _ => None,
+        })
+    }
+}
+
+/// Writes `df` as `.desktop` text, see [`DesktopFile::to_ini_string`].
+/// Parsing the result with [`DesktopFile::parse`] gives back an equal file.
+pub fn to_desktop_string(df: &DesktopFile) -> Result<String, serde_ini::ser::Error> {
+    df.to_ini_string()
+}
+
+/// Why a desktop file couldn't be loaded
+#[derive(Debug)]
+pub enum DesktopError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// The contents aren't a valid desktop file
+    Parse(serde_ini::de::Error),
+}
+
+impl fmt::Display for DesktopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesktopError::Io(e) => write!(f, "couldn't read the file: {}", e),
+            DesktopError::Parse(e) => write!(f, "invalid desktop file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DesktopError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DesktopError::Io(e) => Some(e),
+            DesktopError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for DesktopError {
+    fn from(e: io::Error) -> Self {
+        DesktopError::Io(e)
+    }
+}
+
+impl From<serde_ini::de::Error> for DesktopError {
+    fn from(e: serde_ini::de::Error) -> Self {
+        DesktopError::Parse(e)
+    }
+}
+
+/// Parses the text of a desktop file with [`DesktopFile::parse`], after
+/// dropping a byte order mark and turning CRLF line endings into LF, see
+/// [`scanner::normalize`](crate::scanner::normalize).
+///
+/// ```
+/// use rmenu_ng::desktop_entry::parse_desktop_file;
+///
+/// let contents = "\u{feff}[Desktop Entry]\r\nType=Application\r\nName=Files\r\n";
+/// let file = parse_desktop_file(contents).unwrap();
+/// assert_eq!(file.entry().unwrap().name(""), "Files");
+/// assert!(parse_desktop_file("[Desktop Entry").is_err());
+/// ```
+pub fn parse_desktop_file(contents: &str) -> Result<DesktopFile, DesktopError> {
+    Ok(DesktopFile::parse(&scanner::normalize(contents))?)
+}
+
+/// Reads and parses the desktop file at `path`, like [`parse_desktop_file`].
+/// The [`DesktopFile::id`] is the file's name.
+///
+/// ```
+/// use rmenu_ng::desktop_entry::{DesktopError, parse_desktop_path};
+///
+/// let path = std::env::temp_dir().join("rmenu-doctest-files.desktop");
+/// std::fs::write(&path, "[Desktop Entry]\nType=Application\nName=Files\n").unwrap();
+/// let file = parse_desktop_path(&path).unwrap();
+/// assert_eq!(file.id, "rmenu-doctest-files.desktop");
+/// std::fs::remove_file(&path).unwrap();
+///
+/// assert!(matches!(parse_desktop_path(&path), Err(DesktopError::Io(_))));
+/// ```
+pub fn parse_desktop_path(path: &Path) -> Result<DesktopFile, DesktopError> {
+    let mut file = parse_desktop_file(&fs::read_to_string(path)?)?;
+    if let Some(name) = path.file_name() {
+        file.id = name.to_string_lossy().into_owned();
+    }
+    Ok(file)
+}
+
+impl<'de> Deserialize<'de> for DesktopFile {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SectionsVisitor;
+
+        impl<'de> Visitor<'de> for SectionsVisitor {
+            type Value = DesktopFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of section names to key/value maps")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut sections = BTreeMap::new();
+                while let Some(name) = access.next_key::<String>()? {
+                    let raw: BTreeMap<String, String> = access.next_value()?;
+                    let section = if name == "Desktop Entry" {
+                        Section::Entry {
+                            desktop_entry: deserialize_desktop_entry(raw.into_deserializer())?,
+                        }
+                    } else if let Some(id) = name.strip_prefix("Desktop Action ") {
+                        Section::Action {
+                            action: deserialize_desktop_action(
+                                id.to_string(),
+                                raw.into_deserializer(),
+                            )?,
+                        }
+                    } else {
+                        Section::Other { raw }
+                    };
+                    sections.insert(name, section);
+                }
+                Ok(DesktopFile {
+                    sections,
+                    ..Default::default()
+                })
+            }
+        }
+
+        deserializer.deserialize_map(SectionsVisitor)
+    }
+}
+
+impl Serialize for DesktopFile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.sections.len()))?;
+        // The spec wants [Desktop Entry] first, which sorting alone wouldn't
+        // give ahead of "Desktop Action …"
+        let (entry, others): (Vec<_>, Vec<_>) = self
+            .sections
+            .iter()
+            .partition(|(name, _)| *name == "Desktop Entry");
+        for (name, section) in entry.into_iter().chain(others) {
+            match section {
+                Section::Entry { desktop_entry } => {
+                    map.serialize_entry(name, &serialize_desktop_entry::<S>(desktop_entry)?)?
+                }
+                Section::Action { action } => {
+                    map.serialize_entry(name, &serialize_desktop_action(action))?
+                }
+                Section::Other { raw } => map.serialize_entry(name, raw)?,
+            }
+        }
+        map.end()
+    }
 }
 
 fn deserialize_semicolon_list<'de, D>(deserializer: D) -> Result<Option<SemicolonList>, D::Error>
@@ -466,7 +1028,9 @@ where
         pub other: BTreeMap<String, String>,
     }
 
-    let temp: TempEntry = TempEntry::deserialize(raw_map.clone()).map_err(de::Error::custom)?;
+    // Go through a JSON value so that `Option` fields see a present key as `Some`
+    let value = serde_json::to_value(raw_map).map_err(de::Error::custom)?;
+    let temp = TempEntry::deserialize(value).map_err(de::Error::custom)?;
 
     Ok(DesktopEntry {
         entry_type: temp.entry_type,
@@ -497,13 +1061,37 @@ where
     })
 }
 
-/// Serialize the `DesktopEntry` back into a flatten‐map of key → value.
-fn serialize_desktop_entry<S>(entry: &DesktopEntry, serializer: S) -> Result<S::Ok, S::Error>
+/// The keys of a section in the order they're written out, serialized as a
+/// map
+#[derive(Debug, Default)]
+struct OrderedKeys(Vec<(String, String)>);
+
+impl OrderedKeys {
+    fn insert(&mut self, key: String, value: String) {
+        self.0.push((key, value));
+    }
+}
+
+impl Serialize for OrderedKeys {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Serialize the `DesktopEntry` back into a flatten‐map of key → value, with
+/// the keys in the order the spec lists them and unknown keys last.
+fn serialize_desktop_entry<S>(entry: &DesktopEntry) -> Result<OrderedKeys, S::Error>
 where
     S: Serializer,
 {
-    // Build a single BTreeMap<String, String> with all keys in the right order
-    let mut map = BTreeMap::new();
+    let mut map = OrderedKeys::default();
 
     map.insert("Type".into(), entry.entry_type.clone());
     if let Some(v) = &entry.version {
@@ -518,8 +1106,8 @@ where
             map.insert(format!("Name[{}]", locale), text.clone());
         }
     }
-    if let Some(gen) = &entry.generic_name {
-        for (locale, text) in &gen.0 {
+    if let Some(generic) = &entry.generic_name {
+        for (locale, text) in &generic.0 {
             if locale.is_empty() {
                 map.insert("GenericName".into(), text.clone());
             } else {
@@ -553,15 +1141,10 @@ where
         map.insert("Hidden".into(), h.to_string());
     }
     if let Some(only) = &entry.only_show_in {
-        let s = serde_json::to_string(only).map_err(serde::ser::Error::custom)?;
-        // `serde_json::to_string` wraps in quotes; strip them
-        let s = s.trim_matches('"').to_string();
-        map.insert("OnlyShowIn".into(), s);
+        map.insert("OnlyShowIn".into(), only.to_string());
     }
     if let Some(not) = &entry.not_show_in {
-        let s = serde_json::to_string(not).map_err(serde::ser::Error::custom)?;
-        let s = s.trim_matches('"').to_string();
-        map.insert("NotShowIn".into(), s);
+        map.insert("NotShowIn".into(), not.to_string());
     }
     if let Some(d) = entry.dbus_activatable {
         map.insert("DBusActivatable".into(), d.to_string());
@@ -579,24 +1162,16 @@ where
         map.insert("Terminal".into(), t.to_string());
     }
     if let Some(actions) = &entry.actions {
-        let s = serde_json::to_string(actions).map_err(serde::ser::Error::custom)?;
-        let s = s.trim_matches('"').to_string();
-        map.insert("Actions".into(), s);
+        map.insert("Actions".into(), actions.to_string());
     }
     if let Some(m) = &entry.mime_type {
-        let s = serde_json::to_string(m).map_err(serde::ser::Error::custom)?;
-        let s = s.trim_matches('"').to_string();
-        map.insert("MimeType".into(), s);
+        map.insert("MimeType".into(), m.to_string());
     }
     if let Some(cats) = &entry.categories {
-        let s = serde_json::to_string(cats).map_err(serde::ser::Error::custom)?;
-        let s = s.trim_matches('"').to_string();
-        map.insert("Categories".into(), s);
+        map.insert("Categories".into(), cats.to_string());
     }
     if let Some(imp) = &entry.implements {
-        let s = serde_json::to_string(imp).map_err(serde::ser::Error::custom)?;
-        let s = s.trim_matches('"').to_string();
-        map.insert("Implements".into(), s);
+        map.insert("Implements".into(), imp.to_string());
     }
     if let Some(kw) = &entry.keywords {
         for (locale, text) in &kw.0 {
@@ -625,42 +1200,25 @@ where
         map.insert(k.clone(), v.clone());
     }
 
-    // Wrap as a single‐pair “Desktop Entry” section:
-    let mut wrapper = BTreeMap::new();
-    wrapper.insert("Desktop Entry".to_string(), map);
-
-    wrapper.serialize(serializer)
+    Ok(map)
 }
 
-/// Deserialize any `[Desktop Action <ID>]` section.
-fn deserialize_desktop_action<'de, D>(deserializer: D) -> Result<(String, DesktopAction), D::Error>
+/// Deserialize any `[Desktop Action <ID>]` section. The caller strips the
+/// `<ID>` out of the section name, since the section body doesn't contain it.
+fn deserialize_desktop_action<'de, D>(
+    action_id: String,
+    deserializer: D,
+) -> Result<(String, DesktopAction), D::Error>
 where
     D: Deserializer<'de>,
 {
     // we deserialize into a temporary map key→value
     let mut raw_map: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
 
-    // The “section name” is something like "Desktop Action Gallery".
-    // Serde will have already given us the section’s entire name. We need
-    // to extract the `<ID>` part (“Gallery” in this example) from the caller.
-    // However, in an untagged enum, serde dispatches based on matching the
-    // key “Desktop Action” in the field attribute.  Unfortunately, serde
-    // does not by default give us the “Gallery” part.  The trick is: in
-    // your top‐level map you should have inserted the section as:
-    //    "Desktop Action Gallery" => Section::Action
-    // so here, we only know that debiasing the action ID must be done upstream.
-    // For simplicity, let’s assume serde gives us a special key "__action_id"
-    // in raw_map.  In reality, most INI backends allow you to grab the section
-    // name exactly.  For clarity in this example, we’ll pull the action ID out
-    // of a special field.  In a real implementation you would capture the section
-    // header from your INI reader directly.
-    let action_id = raw_map
-        .remove("__action_id")
-        .ok_or_else(|| de::Error::custom("Missing action ID"))?;
-
     // now extract localized fields out of raw_map
-    let name = deserialize_localized("Name", &mut raw_map)
-        .ok_or_else(|| de::Error::missing_field(&format!("Desktop Action {} → Name", action_id)))?;
+    let name = deserialize_localized("Name", &mut raw_map).ok_or_else(|| {
+        de::Error::custom(format!("Desktop Action {} is missing Name", action_id))
+    })?;
     let icon = deserialize_localized("Icon", &mut raw_map);
 
     let temp = DesktopAction {
@@ -672,18 +1230,13 @@ where
     Ok((action_id, temp))
 }
 
-/// serialize a `(action_id, DesktopAction)` into a section named `"Desktop Action <ID>"`.
-fn serialize_desktop_action<S>(
-    pair: &(String, DesktopAction),
-    serializer: S,
-) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let (action_id, action) = pair;
+/// serialize a `(action_id, DesktopAction)` into the body of its
+/// `"Desktop Action <ID>"` section.
+fn serialize_desktop_action(pair: &(String, DesktopAction)) -> OrderedKeys {
+    let (_, action) = pair;
 
-    // build a flatten map of all keys in the action
-    let mut map = BTreeMap::new();
+    // build a flatten map of all keys in the action, in the spec's order
+    let mut map = OrderedKeys::default();
     // localized Name
     for (locale, text) in &action.name.0 {
         if locale.is_empty() {
@@ -708,11 +1261,7 @@ where
     for (k, v) in &action.other {
         map.insert(k.clone(), v.clone());
     }
-
-    let section_name = format!("Desktop Action {}", action_id);
-    let mut wrapper = BTreeMap::new();
-    wrapper.insert(section_name, map);
-    wrapper.serialize(serializer)
+    map
 }
 
 fn deserialize_opt_bool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
@@ -726,7 +1275,7 @@ where
         match s_lower.as_str() {
             "true" | "1" => Ok(Some(true)),
             "false" | "0" => Ok(Some(false)),
-            other => Err(D::Error::custom(format!(
+            other => Err(de::Error::custom(format!(
                 "Invalid boolean string: {}",
                 other
             ))),
@@ -788,7 +1337,7 @@ Exec=fooview --create-new
         let df: DesktopFile = from_str(EXAMPLE).expect("Failed to parse example");
 
         // check that we got the "Name[de]" localized entry:
-        if let Section::Entry { ref desktop_entry } = df.sections.get("Desktop Entry").unwrap() {
+        if let Section::Entry { desktop_entry } = df.sections.get("Desktop Entry").unwrap() {
             assert_eq!(
                 desktop_entry.name.0.get("de").map(String::as_str).unwrap(),
                 "Foo Betrachter"
@@ -809,5 +1358,222 @@ Exec=fooview --create-new
         assert!(out.contains("Name[de]=Foo Betrachter"));
         assert!(out.contains("[Desktop Action Gallery]"));
         assert!(out.contains("Exec=fooview --gallery"));
+
+        // Keys in the spec's order rather than alphabetical
+        let keys: Vec<&str> = out
+            .lines()
+            .skip_while(|line| *line != "[Desktop Entry]")
+            .skip(1)
+            .take_while(|line| !line.starts_with('['))
+            .filter_map(|line| line.split_once('=').map(|(key, _)| key))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "Type", "Version", "Name", "Name[de]", "Comment", "Icon", "TryExec", "Exec",
+                "Actions", "MimeType"
+            ]
+        );
+    }
+
+    #[test]
+    fn every_action_section_is_listed() {
+        let df = DesktopFile::parse(EXAMPLE).unwrap();
+        let actions: Vec<(&str, &str)> = df
+            .actions()
+            .map(|(id, action)| (id, action.name.0[""].as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("Create", "Create a new Foo!"),
+                ("Gallery", "Browse Gallery")
+            ]
+        );
+        assert_eq!(df.entry().unwrap().name(""), "Foo Viewer");
+
+        let df = DesktopFile::parse("[Desktop Entry]\nType=Application\nName=A\n").unwrap();
+        assert_eq!(df.actions().count(), 0);
+    }
+
+    #[test]
+    fn whitespace_around_values_is_trimmed() {
+        let input = "[Desktop Entry]\nType=Application \nName=Foo \nName[de]=\tFöö  \n\
+                     Exec=foo \"a b \" \nCategories= Network; WebBrowser ;\n\
+                     Keywords=web; browser ;\n";
+        let df = DesktopFile::parse(input).unwrap();
+        let entry = df.entry().unwrap();
+        assert_eq!(entry.entry_type, "Application");
+        assert_eq!(entry.name("C"), "Foo");
+        assert_eq!(entry.name("de"), "Föö");
+        // Only around the value, the quoted argument keeps its space
+        assert_eq!(entry.exec.as_deref(), Some("foo \"a b \""));
+        assert_eq!(
+            entry.categories.as_ref().unwrap().0,
+            ["Network", "WebBrowser"]
+        );
+        assert_eq!(entry.keywords("C"), ["web", "browser"]);
+    }
+
+    #[test]
+    fn strict_parsing_rejects_duplicate_groups() {
+        let input = "[Desktop Entry]\nType=Application\nName=First\nExec=first\n\n\
+                     [Desktop Entry]\nType=Application\nName=Second\nExec=second\n";
+        let e = DesktopFile::parse_strict(input).unwrap_err();
+        assert!(e.to_string().contains("[Desktop Entry]"), "{}", e);
+
+        // The lenient parser keeps the last one
+        let df = DesktopFile::parse(input).unwrap();
+        assert_eq!(df.entry().unwrap().name.0[""], "Second");
+
+        assert!(DesktopFile::parse_strict(EXAMPLE).is_ok());
+    }
+
+    #[test]
+    fn malformed_locale_keys_are_kept_in_other() {
+        let input = "[Desktop Entry]\nType=Application\nName=Foo\nName[de]=Föö\n\
+                     Name[]=empty\nName[=open\nName[de]extra=trailing\nExec=foo\n";
+        let df: DesktopFile = from_str(input).unwrap();
+        let entry = df.entry().unwrap();
+        assert_eq!(entry.name.0.len(), 2);
+        assert_eq!(entry.name.0[""], "Foo");
+        assert_eq!(entry.name.0["de"], "Föö");
+        assert_eq!(entry.other["Name[]"], "empty");
+        assert_eq!(entry.other["Name["], "open");
+        assert_eq!(entry.other["Name[de]extra"], "trailing");
+
+        assert_eq!(locale_of("Name", "Name[a]b]"), None);
+        assert_eq!(locale_of("Name", "NameX"), None);
+    }
+
+    #[test]
+    fn locales_are_matched_ignoring_case() {
+        let input = "[Desktop Entry]\nType=Application\nName=Foo\nName[pt_br]=Fú\n\
+                     Name[DE]=Föö\nName[sr_rs@latin]=Fu\nExec=foo\n";
+        let df = DesktopFile::parse(input).unwrap();
+        let entry = df.entry().unwrap();
+        assert_eq!(entry.name("pt_BR"), "Fú");
+        assert_eq!(entry.name("pt_BR.UTF-8"), "Fú");
+        assert_eq!(entry.name("PT_br"), "Fú");
+        assert_eq!(entry.name("de_AT"), "Föö");
+        assert_eq!(entry.name("sr_RS@latin"), "Fu");
+        assert_eq!(entry.name("pt_PT"), "Foo");
+
+        // Stored and written back the canonical way
+        assert_eq!(entry.name.0["pt_BR"], "Fú");
+        let out = to_string(&df).unwrap();
+        assert!(out.contains("Name[pt_BR]=Fú"), "{}", out);
+        assert!(out.contains("Name[de]=Föö"), "{}", out);
+    }
+
+    #[test]
+    fn unknown_keys_are_kept_in_other() {
+        let input = "[Desktop Entry]\nType=Application\nName=Foo\nExec=foo\n\
+                     X-GNOME-UsesNotifications=true\nX-Foo=bar\nX-Foo[de]=baz\n\
+                     Comand=typo\nNoDisplay=false\n\n\
+                     [Desktop Action New]\nName=New\nExec=foo --new\nX-Action=1\n";
+        let df: DesktopFile = from_str(input).expect("vendor keys should not be rejected");
+
+        let entry = df.entry().unwrap();
+        assert_eq!(entry.other["X-GNOME-UsesNotifications"], "true");
+        assert_eq!(entry.other["X-Foo"], "bar");
+        assert_eq!(entry.other["X-Foo[de]"], "baz");
+        assert_eq!(entry.other["Comand"], "typo");
+        assert_eq!(entry.no_display, Some(false));
+        assert_eq!(df.action("New").unwrap().other["X-Action"], "1");
+
+        // The derived impls, used when an entry is deserialized on its own
+        let value = serde_json::json!({"Type": "Application", "X-Foo": "bar"});
+        let entry: DesktopEntry = serde_json::from_value(value).unwrap();
+        assert_eq!(entry.other["X-Foo"], "bar");
+
+        let out = to_string(&df).unwrap();
+        for line in [
+            "X-GNOME-UsesNotifications=true",
+            "X-Foo=bar",
+            "X-Foo[de]=baz",
+            "Comand=typo",
+            "X-Action=1",
+        ] {
+            assert!(out.contains(line), "{} missing from {}", line, out);
+        }
+    }
+
+    #[test]
+    fn comments_and_blank_lines_survive_a_round_trip() {
+        let input = "# Header comment\n\n\
+                     [Desktop Entry]\n\
+                     Type=Application\n\
+                     # Key comment\n\n\
+                     Name=Foo\n\
+                     Exec=foo\n\n\
+                     ; Section comment\n\
+                     [Desktop Action New]\n\
+                     Name=New\n\
+                     Exec=foo --new\n\n\
+                     # Trailing comment\n";
+        let df = DesktopFile::parse(input).unwrap();
+
+        assert_eq!(df.comments.header, ["# Header comment", ""]);
+        assert_eq!(
+            df.comments.before_section["Desktop Action New"],
+            ["", "; Section comment"]
+        );
+        let key = ("Desktop Entry".to_string(), "Name".to_string());
+        assert_eq!(df.comments.before_key[&key], ["# Key comment", ""]);
+        assert_eq!(df.comments.trailing, ["", "# Trailing comment"]);
+
+        assert_eq!(df.to_ini_string().unwrap(), input);
+    }
+
+    #[test]
+    fn show_in_keys_with_an_unknown_desktop() {
+        let parse = |keys: &str| {
+            let input = format!(
+                "[Desktop Entry]\nType=Application\nName=A\nExec=a\n{}\n",
+                keys
+            );
+            from_str::<DesktopFile>(&input).unwrap()
+        };
+        let only = parse("OnlyShowIn=GNOME;");
+        let not = parse("NotShowIn=KDE;");
+        let (only, not) = (only.entry().unwrap(), not.entry().unwrap());
+
+        // Unset and empty both mean no current desktop
+        for value in ["", ":"] {
+            let desktops = parse_desktops(value);
+            assert!(desktops.is_empty());
+            assert!(!only.should_show(&desktops, false));
+            assert!(only.should_show(&desktops, true));
+            assert!(not.should_show(&desktops, false));
+        }
+
+        let desktops = parse_desktops("ubuntu:GNOME");
+        assert_eq!(desktops, ["ubuntu", "GNOME"]);
+        assert!(only.should_show(&desktops, false));
+        assert!(not.should_show(&desktops, false));
+
+        let desktops = parse_desktops("KDE:Plasma");
+        assert!(!only.should_show(&desktops, true));
+        assert!(!not.should_show(&desktops, true));
+    }
+
+    #[test]
+    fn written_files_read_back_the_same() {
+        let input = "[Desktop Action New]\nName=New\nName[fr]=Nouveau\nExec=foo --new\n\n\
+                     [Desktop Entry]\nType=Application\nName=Foo\nName[fr]=Fou\nName[de]=Fu\n\
+                     Exec=foo %U\nCategories=Utility;Back\\slash\nActions=New;\n\
+                     MimeType=text/plain;text/x-foo;\nX-Vendor=1\n";
+        let df = DesktopFile::parse(input).unwrap();
+        let out = to_desktop_string(&df).unwrap();
+
+        let section = |name: &str| out.find(name).unwrap();
+        assert!(section("[Desktop Entry]") < section("[Desktop Action New]"));
+        assert!(out.contains("Name[de]=Fu\nName[fr]=Fou\n"));
+        assert!(out.contains("Categories=Utility;Back\\slash;\n"));
+        assert!(out.contains("MimeType=text/plain;text/x-foo;\n"));
+        // The blank line before [Desktop Entry] moves up with it, so only the
+        // sections compare equal
+        assert_eq!(DesktopFile::parse(&out).unwrap().sections, df.sections);
     }
 }
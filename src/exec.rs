@@ -0,0 +1,248 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Why an `Exec` value couldn't be split into arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecParseError {
+    /// A double-quoted argument wasn't closed
+    UnterminatedQuote,
+    /// There's no program to run, e.g. `Exec=` or only field codes without
+    /// a value
+    NoProgram,
+}
+
+impl fmt::Display for ExecParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecParseError::UnterminatedQuote => f.write_str("missing closing quote"),
+            ExecParseError::NoProgram => f.write_str("no program to run"),
+        }
+    }
+}
+
+impl std::error::Error for ExecParseError {}
+
+/// Splits an `Exec` value into arguments following the desktop entry spec:
+/// arguments are separated by spaces, and may be enclosed in double quotes,
+/// inside which `"`, `` ` ``, `$` and `\` are escaped with a backslash.
+/// A backslash before any other character is kept as is.
+pub fn tokenize_exec(s: &str) -> Result<Vec<String>, ExecParseError> {
+    let mut args = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(args);
+        }
+        let mut arg = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            if c != '"' {
+                arg.push(c);
+                continue;
+            }
+            loop {
+                match chars.next() {
+                    None => return Err(ExecParseError::UnterminatedQuote),
+                    Some('"') => break,
+                    Some('\\') => match chars.next_if(|c| matches!(c, '"' | '`' | '$' | '\\')) {
+                        Some(escaped) => arg.push(escaped),
+                        None => arg.push('\\'),
+                    },
+                    Some(c) => arg.push(c),
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Splits an `Exec` value into argv with [`tokenize_exec`] and expands its
+/// field codes.
+///
+/// `%f`/`%u` take the first of `files`, `%F`/`%U` take all of them as separate
+/// arguments, `%i` becomes `--icon <icon>`, `%c` the entry's name and `%%` a
+/// literal `%`. Codes without a value, and the deprecated ones, are dropped.
+/// Fails with [`ExecParseError::NoProgram`] if that leaves no program, or
+/// an empty one.
+pub fn expand_exec(
+    exec: &str,
+    files: &[&str],
+    icon: Option<&str>,
+    name: &str,
+) -> Result<Vec<String>, ExecParseError> {
+    let mut argv = Vec::new();
+    for arg in tokenize_exec(exec)? {
+        match arg.as_str() {
+            "%F" | "%U" => argv.extend(files.iter().map(|f| f.to_string())),
+            "%i" => {
+                // An empty Icon= is as good as none
+                if let Some(icon) = icon.filter(|icon| !icon.is_empty()) {
+                    argv.push("--icon".to_string());
+                    argv.push(icon.to_string());
+                }
+            }
+            _ => {
+                let expanded = expand_field_codes(&arg, files, name);
+                // An argument that was nothing but an empty field code goes away entirely
+                if !expanded.is_empty() || !arg.contains('%') {
+                    argv.push(expanded);
+                }
+            }
+        }
+    }
+    if argv.first().is_none_or(|program| program.trim().is_empty()) {
+        return Err(ExecParseError::NoProgram);
+    }
+    Ok(argv)
+}
+
+/// Expands the field codes that may appear inside a single argument.
+fn expand_field_codes(arg: &str, files: &[&str], name: &str) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('f') | Some('u') => out.push_str(files.first().copied().unwrap_or_default()),
+            Some('c') => out.push_str(name),
+            // %k (file location), %F/%U/%i inside a word and the deprecated codes
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Joins `args` into a single command line for `sh -c`, double-quoting any
+/// argument containing characters the shell would otherwise interpret.
+pub fn join_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_arg(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Resolves `program` to an executable file. Absolute paths are checked as
+/// they are, bare names are looked up in each directory of `path`, a
+/// `$PATH`-style list.
+pub fn find_executable(program: &str, path: &OsStr) -> Option<PathBuf> {
+    let program = Path::new(program);
+    if program.is_absolute() {
+        return is_executable(program).then(|| program.to_path_buf());
+    }
+    std::env::split_paths(path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_quoted_arguments() {
+        assert_eq!(
+            tokenize_exec(r#"sh -c "echo \"hi\"""#).unwrap(),
+            ["sh", "-c", r#"echo "hi""#]
+        );
+        assert_eq!(
+            tokenize_exec(r#""/opt/My App/app"  --flag"#).unwrap(),
+            ["/opt/My App/app", "--flag"]
+        );
+        assert_eq!(
+            tokenize_exec(r#"app "\$HOME \`x\` \\ \n""#).unwrap(),
+            ["app", r#"$HOME `x` \ \n"#]
+        );
+        assert_eq!(tokenize_exec(r#"app "" x"#).unwrap(), ["app", "", "x"]);
+        assert_eq!(
+            tokenize_exec(r#"app pre"quoted"post"#).unwrap(),
+            ["app", "prequotedpost"]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert_eq!(
+            tokenize_exec(r#"sh -c "echo hi"#),
+            Err(ExecParseError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let argv = expand_exec("app %U --name %c %i %k", &["a", "b"], Some("icon"), "App");
+        assert_eq!(
+            argv.unwrap(),
+            ["app", "a", "b", "--name", "App", "--icon", "icon"]
+        );
+    }
+
+    #[test]
+    fn icon_code_is_dropped_without_an_icon() {
+        assert_eq!(
+            expand_exec("foo %i", &[], Some("foo-icon"), "Foo").unwrap(),
+            ["foo", "--icon", "foo-icon"]
+        );
+        assert_eq!(expand_exec("foo %i", &[], None, "Foo").unwrap(), ["foo"]);
+        assert_eq!(
+            expand_exec("foo %i", &[], Some(""), "Foo").unwrap(),
+            ["foo"]
+        );
+    }
+
+    #[test]
+    fn file_codes_without_files_leave_no_argument() {
+        for (exec, expected) in [
+            ("foo %f", &["foo"][..]),
+            ("foo %F", &["foo"]),
+            ("foo --flag %U", &["foo", "--flag"]),
+        ] {
+            assert_eq!(expand_exec(exec, &[], None, "Foo").unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_exec_without_a_program() {
+        for exec in ["", "   ", "%f", "\"\" --flag"] {
+            assert_eq!(
+                expand_exec(exec, &[], None, "Foo"),
+                Err(ExecParseError::NoProgram),
+                "{:?}",
+                exec
+            );
+        }
+    }
+}
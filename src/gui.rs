@@ -1,85 +1,1689 @@
-use crate::config::{AppConfig, ColorsConfig};
-use eframe::egui::{self, CentralPanel, Context, FontData, FontDefinitions, FontFamily, TextEdit};
+use crate::cli::Args;
+use crate::command::Command;
+use crate::config::{
+    AppConfig, ColorsConfig, ConfigWatcher, InputPosition, LabelMode, Layout, PositionMode,
+    expand_path,
+};
+use crate::core::{self, ShortQuery};
+use crate::exec::join_args;
+use crate::history::{self, History};
+use crate::keybindings::{Action, Keybindings};
+use crate::launcher;
+use crate::plugins::{calc, shell, web};
+use crate::state;
+use eframe::egui::{
+    Align, CentralPanel, Color32, Context, Event, FontData, FontDefinitions, FontFamily, FontId,
+    Frame, Key, Label, Modifiers, RichText, ScrollArea, Stroke, TextEdit, TextFormat, TextStyle,
+    TextWrapMode, TopBottomPanel, Ui, Vec2, ViewportCommand, pos2,
+    text::{CCursor, CCursorRange, LayoutJob},
+    vec2,
+};
 use eframe::{App, CreationContext};
+use log::{debug, error, warn};
+use std::collections::HashSet;
+use std::ops::Range;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the config files are checked for changes
+const CONFIG_POLL_INTERVAL: f64 = 1.0;
+
+/// How long the banner reporting problems stays up, in seconds
+const BANNER_DURATION: f64 = 8.0;
+
+/// The entries to list, loaded off the UI thread
+pub struct MenuEntries {
+    pub commands: Vec<Command>,
+    /// Category headings and the ranges of `commands` listed under them
+    pub groups: Vec<(String, Range<usize>)>,
+    /// Problems met while loading, shown in a banner for a while
+    pub errors: Vec<String>,
+}
+
+/// Produces the entries for a query typed after a mode's prefix
+pub type ModeSource = fn(&str) -> Vec<Command>;
+
+/// An application's desktop actions, listed in place of the entries until
+/// one is picked or the picker is cancelled
+struct ActionPicker {
+    actions: Vec<Command>,
+    /// The query and selection to go back to
+    query: String,
+    selected: usize,
+}
+
+/// The thread loading the entries, and where it sends them
+struct Loader {
+    handle: JoinHandle<()>,
+    entries: Receiver<MenuEntries>,
+}
 
 pub struct RMenuApp {
     input_text: String,
     selected_index: usize,
-    options: Vec<String>,
+    /// Index of the first row currently scrolled into view
+    first_visible: usize,
+    /// Rows in view as of the last frame, used to keep the selection on screen
+    visible_rows: usize,
+    /// Set when the selection moved by keyboard and should be scrolled into view
+    scroll_to_selected: bool,
+    /// Whether the horizontal bar has been stretched to the monitor width
+    sized_to_screen: bool,
+    /// Whether the window has been moved to its `position_mode` placement
+    placed: bool,
+    /// Where the window was last seen once placed and shown, saved on exit
+    /// with `remember_position`
+    position: Option<(f32, f32)>,
+    /// The window height last asked for to fit `lines` rows
+    fitted_height: Option<f32>,
+    /// Height of the `show_command_preview` line, which `fit_to_rows` makes
+    /// room for
+    preview_height: f32,
+    /// Set once the window has been focused, so the focus changes while it's
+    /// being mapped don't close it
+    was_focused: bool,
+    /// When the last key press or mouse movement happened, for
+    /// `idle_timeout_secs`
+    last_activity: f64,
+    /// When the query was last typed or edited, `None` until it is. A lone
+    /// match is only launched automatically from then on.
+    typed_at: Option<f64>,
+    entries: Vec<Command>,
+    /// How many of `entries` came from the loader. Any after them were made
+    /// for the current query.
+    loaded: usize,
+    /// Query prefixes that switch to another source than the loaded entries
+    modes: Vec<(char, ModeSource)>,
+    /// Category headings and the ranges of `entries` listed under them
+    groups: Vec<(String, Range<usize>)>,
+    /// `groups` as the rows drawn in the grouped view
+    list_items: Vec<core::ListItem>,
+    /// Indices into `entries` that match the current query, best match first
+    options: Vec<usize>,
+    /// Byte offsets of the matched characters in each option's display text
+    highlights: Vec<Vec<usize>>,
+    /// Whether the query was shorter than `min_query_len` last time the
+    /// options were updated
+    short_query: bool,
+    /// The last query's matches, so typing on only re-checks those
+    filter_cache: core::FilterCache,
+    /// Picks of loaded entries, kept between runs. Empty and unsaved for
+    /// `--stdin` and `--password`.
+    history: History,
+    /// Frecency of each loaded entry, indexed like `entries`
+    frecency: Vec<f64>,
+    /// Indices into `options` marked with `--multi`, cleared when they change
+    marked: HashSet<usize>,
+    /// Index in `entries` of the calculator result appended for an arithmetic query
+    calc_entry: Option<usize>,
+    /// Set while picking one of an application's actions
+    picker: Option<ActionPicker>,
+    /// Set until the entries have arrived
+    loader: Option<Loader>,
+    /// Loading or launch problems to show, and the time to stop showing them
+    banner: Option<(String, f64)>,
+    /// Select the whole query once the input field exists, see `remember_query`
+    select_query: bool,
+    /// Set when a selection was made, rather than the menu being cancelled
+    accepted: Arc<AtomicBool>,
     colors: ColorsConfig,
     app_config: AppConfig,
+    keybindings: Keybindings,
+    args: Args,
+    /// `None` when there's no config directory, so nothing to reload
+    watcher: Option<ConfigWatcher>,
+    last_config_poll: f64,
 }
 
 impl RMenuApp {
-    pub fn new(cc: &CreationContext<'_>, colors: ColorsConfig, app_config: AppConfig) -> Self {
-        // Customize fonts if needed
-        let mut fonts = FontDefinitions::default();
-        fonts.font_data.insert(
-            "Ubuntu Medium".to_string(),
-            Arc::new(FontData::from_static(include_bytes!(
-                "../assets/Ubuntu-M.ttf"
-            ))),
-        );
-        fonts
-            .families
-            .entry(FontFamily::Proportional)
-            .or_default()
-            .insert(0, "Ubuntu Medium".to_string());
-        cc.egui_ctx.set_fonts(fonts);
-
-        Self {
+    pub fn new(
+        cc: &CreationContext<'_>,
+        colors: ColorsConfig,
+        app_config: AppConfig,
+        args: Args,
+        watcher: Option<ConfigWatcher>,
+        load: impl FnOnce() -> MenuEntries + Send + 'static,
+        accepted: Arc<AtomicBool>,
+    ) -> Self {
+        cc.egui_ctx
+            .set_fonts(font_definitions(&expand_path(&app_config.font_name)));
+        apply_font_size(&cc.egui_ctx, colors.font_size);
+
+        // Scanning can take a while on a cold cache, so the window shows up
+        // right away and the entries are filled in when they're ready
+        let (sender, entries) = mpsc::channel();
+        let ctx = cc.egui_ctx.clone();
+        let handle = thread::spawn(move || {
+            // Fails only if the window was closed first, in which case nobody's waiting
+            if sender.send(load()).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+
+        let mut app = Self {
             input_text: String::new(),
             selected_index: 0,
+            first_visible: 0,
+            visible_rows: 0,
+            scroll_to_selected: false,
+            sized_to_screen: false,
+            placed: false,
+            position: None,
+            fitted_height: None,
+            preview_height: 0.0,
+            was_focused: false,
+            last_activity: 0.0,
+            typed_at: None,
+            entries: Vec::new(),
+            loaded: 0,
+            modes: Vec::new(),
+            groups: Vec::new(),
+            list_items: Vec::new(),
             options: Vec::new(),
+            highlights: Vec::new(),
+            short_query: false,
+            filter_cache: core::FilterCache::default(),
+            history: History::default(),
+            frecency: Vec::new(),
+            marked: HashSet::new(),
+            calc_entry: None,
+            picker: None,
+            loader: Some(Loader { handle, entries }),
+            banner: None,
+            select_query: false,
+            accepted,
             colors,
+            keybindings: Keybindings::from_config(&app_config.keybindings),
             app_config,
+            args,
+            watcher,
+            last_config_poll: 0.0,
+        };
+        // Piped lines are matched as typed, prefix characters included
+        if !app.args.stdin {
+            app.register_mode(':', shell::run);
+            app.register_mode('?', web::search);
+        }
+        // Piped lines differ from script to script, so they aren't tracked
+        if !app.args.stdin
+            && !app.args.password
+            && let Some(path) = state::history_path()
+        {
+            app.history = History::load(path);
+        }
+        if app.app_config.remember_query
+            && !app.args.password
+            && let Some(path) = state::last_query_path()
+        {
+            app.input_text = state::load_last_query(&path);
+            app.select_query = !app.input_text.is_empty();
+        }
+        app.update_options();
+        app
+    }
+
+    /// Makes queries starting with `prefix` list what `source` returns for the
+    /// rest of the query, instead of matching the loaded entries. A later
+    /// registration for the same prefix replaces the earlier one.
+    pub fn register_mode(&mut self, prefix: char, source: ModeSource) {
+        self.modes.retain(|(p, _)| *p != prefix);
+        self.modes.push((prefix, source));
+    }
+
+    /// Swaps in the entries once the loader thread has sent them. The thread is
+    /// joined here; if the window closes first it's left to finish on its own,
+    /// since reading stdin may block indefinitely.
+    fn receive_entries(&mut self, ctx: &Context) {
+        let Some(loader) = &self.loader else {
+            return;
+        };
+        let entries = match loader.entries.try_recv() {
+            Ok(entries) => Some(entries),
+            Err(TryRecvError::Empty) => return,
+            // The loader panicked, and has already reported it on stderr
+            Err(TryRecvError::Disconnected) => None,
+        };
+        let errors = match &entries {
+            Some(entries) => entries.errors.clone(),
+            None => vec!["Loading the entries failed".to_string()],
+        };
+        if !errors.is_empty() {
+            self.show_error(ctx, errors.join("\n"));
+        }
+        if let Some(loader) = self.loader.take() {
+            let _ = loader.handle.join();
+        }
+        if let Some(entries) = entries {
+            self.calc_entry = None;
+            self.loaded = entries.commands.len();
+            self.entries = entries.commands;
+            self.list_items = core::list_items(&entries.groups);
+            self.groups = entries.groups;
+            self.filter_cache = core::FilterCache::default();
+            self.update_frecency();
+            self.update_options();
+        }
+    }
+
+    /// Scores the loaded entries from the history, for ranking them
+    fn update_frecency(&mut self) {
+        let now = history::now();
+        self.frecency = self.entries[..self.loaded]
+            .iter()
+            .map(|e| self.history.frecency(&self.history_key(e), now))
+            .collect();
+    }
+
+    /// Picks up edits to `colors.ron`/`app.ron` without a restart. Files that fail
+    /// to parse are ignored so the last good config stays in effect.
+    fn reload_config(&mut self, ctx: &Context) {
+        let now = ctx.input(|i| i.time);
+        if now - self.last_config_poll < CONFIG_POLL_INTERVAL {
+            return;
+        }
+        self.last_config_poll = now;
+
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if let Some(colors) = watcher.poll_colors() {
+            apply_font_size(ctx, colors.font_size);
+            self.colors = colors;
+        }
+        if let Some(mut app_config) = watcher.poll_app() {
+            self.args.apply(&mut app_config);
+            self.keybindings = Keybindings::from_config(&app_config.keybindings);
+            if app_config.font_name != self.app_config.font_name {
+                ctx.set_fonts(font_definitions(&expand_path(&app_config.font_name)));
+            }
+            // The match mode or search fields may have changed
+            self.filter_cache = core::FilterCache::default();
+            self.app_config = app_config;
+            self.update_frecency();
+            self.update_options();
         }
     }
 
     fn update_options(&mut self) {
-        // Placeholder for filtering logic
-        self.options = vec![
-            "Option 1".to_string(),
-            "Option 2".to_string(),
-            "Option 3".to_string(),
-        ]
-        .into_iter()
-        .filter(|opt| opt.to_lowercase().contains(&self.input_text.to_lowercase()))
-        .collect();
+        self.marked.clear();
+        self.calc_entry = None;
+        self.entries.truncate(self.loaded);
+
+        if let Some(picker) = &self.picker {
+            let displays = picker.actions.iter().map(Command::display);
+            let matches = core::filtered(
+                displays,
+                &self.input_text,
+                self.app_config.match_mode,
+                0,
+                &[],
+                &mut core::FilterCache::default(),
+            );
+            self.options = (self.loaded..self.loaded + matches.len()).collect();
+            self.highlights.clear();
+            for (i, highlights) in matches {
+                self.entries.push(picker.actions[i].clone());
+                self.highlights.push(highlights);
+            }
+            if self.selected_index >= self.options.len() {
+                self.selected_index = 0;
+            }
+            return;
+        }
+
+        let mut query = self.input_text.chars();
+        let mode_source = query
+            .next()
+            .and_then(|first| self.modes.iter().find(|(prefix, _)| *prefix == first));
+        if let Some((_, source)) = mode_source {
+            let results = source(query.as_str());
+            self.options = (self.loaded..self.loaded + results.len()).collect();
+            self.highlights = vec![Vec::new(); results.len()];
+            self.entries.extend(results);
+            self.selected_index = 0;
+            return;
+        }
+
+        // The grouped view lists every entry, so it needs all of them as options
+        let limit = if self.showing_groups() {
+            0
+        } else {
+            self.app_config.max_results
+        };
+        // Starting over at the top when the list switches between the
+        // short-query one and matches
+        let short = core::too_short(&self.input_text, self.app_config.min_query_len);
+        if short != self.short_query {
+            self.short_query = short;
+            self.selected_index = 0;
+        }
+        let query = if short { "" } else { self.input_text.as_str() };
+        if short && self.app_config.short_query == ShortQuery::Nothing {
+            self.options.clear();
+            self.highlights.clear();
+        } else if query.is_empty() {
+            // Piped lines keep the order they came in, and the grouped view
+            // draws the entries by their index in `groups`
+            let keep_order = self.args.stdin || self.showing_groups();
+            self.options = core::unfiltered(
+                &self.entries,
+                &self.frecency,
+                self.app_config.sort,
+                limit,
+                keep_order,
+            );
+            self.highlights = vec![Vec::new(); self.options.len()];
+        } else {
+            let fields = &self.app_config.search_fields;
+            let texts: Vec<Vec<&str>> = self
+                .entries
+                .iter()
+                .map(|entry| core::search_texts(entry, fields))
+                .collect();
+            (self.options, self.highlights) = core::filtered_fields(
+                &texts,
+                query,
+                self.app_config.match_mode,
+                limit,
+                &self.frecency,
+                &mut self.filter_cache,
+            )
+            .into_iter()
+            .unzip();
+        }
+        if let Some(result) = calc::try_eval(&self.input_text) {
+            let index = self.entries.len();
+            self.entries
+                .push(Command::new("calc", result.clone(), result));
+            self.options.insert(0, index);
+            self.highlights.insert(0, Vec::new());
+            self.calc_entry = Some(index);
+        }
+        // Not with --index, since a typed command has no position to print
+        if self.options.is_empty() && self.app_config.exec_fallback && !self.args.index && !short {
+            let fallback = shell::run(&self.input_text);
+            self.options = (self.entries.len()..self.entries.len() + fallback.len()).collect();
+            self.highlights = vec![Vec::new(); fallback.len()];
+            self.entries.extend(fallback);
+        }
+        if self.selected_index >= self.options.len() {
+            self.selected_index = 0;
+        }
+    }
+
+    /// The display text of the option at `index`, with the characters matching
+    /// the query drawn in the highlight color, or underlined on the selected row
+    /// where the background already uses it. Marked options get a check mark.
+    fn option_label(&self, ui: &Ui, index: usize, selected: bool) -> LayoutJob {
+        let text = self.entries[self.options[index]].display();
+        let highlights = &self.highlights[index];
+        let font_id = TextStyle::Button.resolve(ui.style());
+        let text_color = to_color32(self.colors.text);
+        let normal = TextFormat::simple(font_id.clone(), text_color);
+        let matched = if selected {
+            TextFormat {
+                underline: Stroke::new(1.0, text_color),
+                ..normal.clone()
+            }
+        } else {
+            TextFormat::simple(font_id, to_color32(self.colors.highlight))
+        };
+
+        let mut job = LayoutJob::default();
+        if self.marked.contains(&index) {
+            job.append("✔ ", 0.0, matched.clone());
+        }
+        for (offset, c) in text.char_indices() {
+            let format = if highlights.contains(&offset) {
+                matched.clone()
+            } else {
+                normal.clone()
+            };
+            job.append(&text[offset..offset + c.len_utf8()], 0.0, format);
+        }
+        job
+    }
+
+    /// Runs the option at `index`, or prints its command with `--print`, then
+    /// closes, unless `AppConfig::stay_open` keeps it open after running.
+    /// A calculator result is always printed. With `--clipboard` the option is
+    /// copied instead, and with `--password` the typed text is printed. With
+    /// `--multi` the marked options, or the one at `index` if none are, are
+    /// printed one per line. `--index` prints their positions in the input.
+    /// `--dry-run` tells on stderr what would run instead of running it.
+    fn activate(&mut self, ctx: &Context, index: usize) {
+        if self.args.password {
+            println!("{}", self.input_text);
+            self.accept(ctx);
+            return;
+        }
+        if self.args.clipboard {
+            self.copy(ctx, index);
+            return;
+        }
+        if self.args.multi {
+            let mut marked: Vec<usize> = self.marked.iter().copied().collect();
+            if marked.is_empty() {
+                marked.push(index);
+            }
+            marked.sort_unstable();
+            for i in marked {
+                if let Some(&entry) = self.options.get(i) {
+                    self.record_use(entry);
+                    let command = &self.entries[entry];
+                    if self.args.index && self.calc_entry != Some(entry) {
+                        println!("{}", command.key());
+                    } else {
+                        println!("{}", command);
+                    }
+                }
+            }
+            self.accept(ctx);
+            return;
+        }
+        let Some(&entry) = self.options.get(index) else {
+            return;
+        };
+        let command = self.entries[entry].command();
+        let calc = self.calc_entry == Some(entry);
+        let printed = self.args.index || self.args.print || calc;
+        if self.args.index && !calc {
+            println!("{}", self.entries[entry].key());
+        } else if self.args.print || calc {
+            println!("{}", command);
+        } else if !self.args.dry_run
+            && let Err(e) = launcher::launch_entry(
+                &self.entries[entry],
+                self.use_shell(entry),
+                &self.app_config.terminal,
+            )
+        {
+            // Staying open, so the banner says why nothing happened
+            error!("failed to launch {}: {}", command, e);
+            let name = self.entries[entry].display();
+            self.show_error(ctx, format!("Couldn't launch {}: {}", name, e));
+            return;
+        }
+        self.record_use(entry);
+        if self.args.dry_run && !calc {
+            self.report_dry_run(entry);
+        }
+        if self.app_config.stay_open && !printed {
+            self.reset_after_launch();
+        } else {
+            self.accept(ctx);
+        }
+    }
+
+    /// Starts over with an empty query after a launch with
+    /// `AppConfig::stay_open`, ranking the entries by the updated history.
+    /// rmenu exits successfully whenever it's closed from then on.
+    fn reset_after_launch(&mut self) {
+        self.history.save();
+        self.accepted.store(true, Ordering::SeqCst);
+        self.picker = None;
+        self.input_text.clear();
+        self.entries.truncate(self.loaded);
+        self.update_frecency();
+        self.filter_cache = core::FilterCache::default();
+        self.update_options();
+        self.selected_index = 0;
+        self.scroll_to_selected = true;
+    }
+
+    /// Lists the desktop actions of the option at `index` in place of the
+    /// entries, if it has any. The query starts out empty.
+    fn show_actions(&mut self, index: usize) {
+        let Some(&entry) = self.options.get(index) else {
+            return;
+        };
+        let actions = self.entries[entry].actions();
+        if self.picker.is_some() || actions.is_empty() {
+            return;
+        }
+        self.picker = Some(ActionPicker {
+            actions: actions.to_vec(),
+            query: std::mem::take(&mut self.input_text),
+            selected: index,
+        });
+        self.selected_index = 0;
+        self.update_options();
+        self.scroll_to_selected = true;
+    }
+
+    /// Goes back from the action picker to the entries as they were left
+    fn close_actions(&mut self) {
+        let Some(picker) = self.picker.take() else {
+            return;
+        };
+        self.input_text = picker.query;
+        self.update_options();
+        if picker.selected < self.options.len() {
+            self.selected_index = picker.selected;
+        }
+        self.scroll_to_selected = true;
+    }
+
+    /// Tells on stderr what launching the entry at `entry` would run, for
+    /// `--dry-run`
+    fn report_dry_run(&self, entry: usize) {
+        let command = &self.entries[entry];
+        match launcher::entry_argv(command, self.use_shell(entry), &self.app_config.terminal) {
+            Ok(argv) => match command.working_dir() {
+                Some(dir) => eprintln!("rmenu: would run {:?} in {}", argv, expand_path(dir)),
+                None => eprintln!("rmenu: would run {:?}", argv),
+            },
+            Err(e) => eprintln!("rmenu: couldn't run {}: {}", command.command(), e),
+        }
+        if command.dbus_activatable() && cfg!(feature = "dbus") {
+            eprintln!("rmenu: would try D-Bus activation first");
+        }
+    }
+
+    /// What the selected option would run, as a command line, for
+    /// `AppConfig::show_command_preview`. `None` for calculator results, which
+    /// are printed, and commands that can't be split into arguments.
+    fn command_preview(&self) -> Option<String> {
+        let &entry = self.options.get(self.selected_index)?;
+        if self.calc_entry == Some(entry) {
+            return None;
+        }
+        let argv = launcher::entry_argv(
+            &self.entries[entry],
+            self.use_shell(entry),
+            &self.app_config.terminal,
+        )
+        .ok()?;
+        Some(join_args(&argv))
+    }
+
+    /// Shows the selected option's command line in a dim line along the
+    /// bottom of the window, cut off at its width.
+    fn show_command_preview(&mut self, ctx: &Context, frame: Frame) {
+        let enabled = self.app_config.show_command_preview
+            && self.app_config.layout == Layout::Vertical
+            && !self.args.password;
+        let preview = enabled.then(|| self.command_preview()).flatten();
+        let Some(preview) = preview else {
+            self.preview_height = 0.0;
+            return;
+        };
+        let panel = TopBottomPanel::bottom("command_preview")
+            .frame(frame)
+            .show_separator_line(false)
+            .show(ctx, |ui| {
+                ui.add(Label::new(RichText::new(preview).weak()).truncate());
+            });
+        self.preview_height = panel.response.rect.height();
+    }
+
+    /// Whether the entry at `entry` is run through the shell. The `:` mode's
+    /// entries always are, since they're typed as shell commands.
+    fn use_shell(&self, entry: usize) -> bool {
+        self.app_config.shell || self.entries[entry].key().starts_with(shell::KEY_PREFIX)
+    }
+
+    /// Counts a pick of the entry at `entry` in the history, if it's one of
+    /// the loaded entries rather than a mode's or the calculator's
+    fn record_use(&mut self, entry: usize) {
+        if entry < self.loaded {
+            let key = self.history_key(&self.entries[entry]);
+            self.history.record(&key, history::now());
+        }
+    }
+
+    /// Drops the option at `index` from the history and saves it, ranking the
+    /// list again. Only loaded entries are in the history to begin with.
+    fn forget(&mut self, index: usize) {
+        let Some(&entry) = self.options.get(index) else {
+            return;
+        };
+        if entry >= self.loaded {
+            return;
+        }
+        let key = self.history_key(&self.entries[entry]);
+        if self.history.forget(&key) {
+            self.history.save();
+            self.update_frecency();
+            self.update_options();
+            self.scroll_to_selected = true;
+        }
+    }
+
+    /// What picks of `command` are recorded under. Lines from stdin are
+    /// always keyed on themselves.
+    fn history_key(&self, command: &Command) -> String {
+        if self.args.stdin {
+            command.key().to_string()
+        } else {
+            core::frecency_key(command, self.app_config.frecency_key)
+        }
+    }
+
+    /// Closes the window after a selection, so rmenu exits successfully.
+    fn accept(&self, ctx: &Context) {
+        self.history.save();
+        if self.app_config.remember_query
+            && !self.args.password
+            && let Some(path) = state::last_query_path()
+        {
+            state::save_last_query(&path, &self.input_text);
+        }
+        self.accepted.store(true, Ordering::SeqCst);
+        ctx.send_viewport_cmd(ViewportCommand::Close);
+    }
+
+    /// Puts the display text of the option at `index` on the clipboard, then
+    /// closes.
+    ///
+    /// egui can't tell whether that worked, and on X11 and Wayland the text
+    /// only outlives rmenu if a clipboard manager takes it over. So it's printed
+    /// to stdout too, where e.g. `rmenu --clipboard | wl-copy` can pick it up.
+    fn copy(&mut self, ctx: &Context, index: usize) {
+        let Some(&entry) = self.options.get(index) else {
+            return;
+        };
+        self.record_use(entry);
+        let entry = &self.entries[entry];
+        ctx.copy_text(entry.to_string());
+        println!("{}", entry);
+        self.accept(ctx);
+    }
+
+    /// `Ctrl+C` copies the highlighted option. egui turns the shortcut into a
+    /// copy event, which is taken away from the input field here, so it can't
+    /// be used to copy part of the query.
+    fn handle_copy(&mut self, ctx: &Context) {
+        let copy = ctx.input_mut(|i| {
+            let before = i.events.len();
+            i.events.retain(|e| *e != Event::Copy);
+            i.events.len() != before
+        });
+        if copy {
+            self.copy(ctx, self.selected_index);
+        }
+    }
+
+    /// Notes where the window is, once it's been moved to its placement and
+    /// focused. Positions from before that are still the initial one or
+    /// made up by the platform, and aren't worth saving.
+    fn track_position(&mut self, ctx: &Context) {
+        let placed = self.placed
+            || (self.app_config.position_mode == PositionMode::Absolute
+                && !self.app_config.layer_shell.enabled);
+        let (focused, rect) = ctx.input(|i| (i.viewport().focused, i.viewport().outer_rect));
+        if placed
+            && focused == Some(true)
+            && let Some(rect) = rect
+        {
+            self.position = Some((rect.min.x, rect.min.y));
+        }
+    }
+
+    /// Closes the window when it goes from focused to unfocused.
+    fn handle_focus_loss(&mut self, ctx: &Context) {
+        let Some(focused) = ctx.input(|i| i.viewport().focused) else {
+            return;
+        };
+        if focused {
+            self.was_focused = true;
+        } else if self.was_focused {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        }
+    }
+
+    /// Closes the window once there's been no input for `timeout` seconds,
+    /// and otherwise asks to be woken up when it would run out.
+    fn handle_idle_timeout(&mut self, ctx: &Context, timeout: u64) {
+        let (now, active) = ctx.input(|i| {
+            let active = i.events.iter().any(|event| {
+                matches!(
+                    event,
+                    Event::Key { .. }
+                        | Event::Text(_)
+                        | Event::Paste(_)
+                        | Event::PointerMoved(_)
+                        | Event::PointerButton { .. }
+                        | Event::MouseWheel { .. }
+                )
+            });
+            (i.time, active)
+        });
+        if active {
+            self.last_activity = now;
+        }
+        let remaining = self.last_activity + timeout as f64 - now;
+        if remaining <= 0.0 {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        } else {
+            ctx.request_repaint_after(Duration::from_secs_f64(remaining));
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.options.is_empty() {
+            return;
+        }
+        self.selected_index = if self.showing_groups() {
+            core::move_selection_in(&self.list_items, self.selected_index, delta)
+        } else {
+            core::move_selection(self.selected_index, delta, self.options.len())
+        };
+        self.scroll_to_selected = true;
+    }
+
+    /// Launches the only entry matching the typed query once it has stayed
+    /// the same for `AppConfig::auto_accept_delay_ms`, if set. Only a loaded
+    /// entry counts, and not until every entry is loaded, so the match is
+    /// really the only one.
+    fn handle_auto_accept(&mut self, ctx: &Context) {
+        let (Some(typed_at), Some(delay_ms)) =
+            (self.typed_at, self.app_config.auto_accept_delay_ms)
+        else {
+            return;
+        };
+        let lone = match self.options[..] {
+            [entry] => entry < self.loaded,
+            _ => false,
+        };
+        if !lone
+            || self.input_text.is_empty()
+            || self.short_query
+            || self.loader.is_some()
+            || self.picker.is_some()
+            || self.args.multi
+        {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        let remaining = core::settle_time(typed_at, now, delay_ms);
+        if remaining.is_zero() {
+            self.typed_at = None;
+            self.activate(ctx, 0);
+        } else {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+
+    /// Moves the selection, opens the action picker and cancels according to
+    /// the key bindings, see `AppConfig::escape_clears_first`. Cancelling the
+    /// action picker goes back to the entries. In the
+    /// horizontal layout Left/Right also move, taken from the input field.
+    fn handle_navigation(&mut self, ctx: &Context) {
+        let horizontal = self.app_config.layout == Layout::Horizontal;
+        let page = self.visible_rows.max(1) as isize;
+        let (up, down, page_up, page_down, cancel, toggles, actions, edit, forget) =
+            ctx.input_mut(|i| {
+                let mut up = self.keybindings.consume(i, Action::MoveUp);
+                let mut down = self.keybindings.consume(i, Action::MoveDown);
+                if horizontal {
+                    up += i.count_and_consume_key(Modifiers::NONE, Key::ArrowLeft);
+                    down += i.count_and_consume_key(Modifiers::NONE, Key::ArrowRight);
+                }
+                (
+                    up as isize,
+                    down as isize,
+                    self.keybindings.consume(i, Action::PageUp) as isize,
+                    self.keybindings.consume(i, Action::PageDown) as isize,
+                    self.keybindings.consume(i, Action::Cancel) > 0,
+                    self.keybindings.consume(i, Action::ToggleMark),
+                    // Before the accept key, which would take Shift+Enter as Enter
+                    self.keybindings.consume(i, Action::ShowActions) > 0,
+                    self.keybindings.consume(i, Action::EditConfig) > 0,
+                    // Before the input field, which would delete a character
+                    self.keybindings.consume(i, Action::ForgetEntry) > 0,
+                )
+            });
+        if self.args.multi && toggles % 2 == 1 && self.selected_index < self.options.len() {
+            let index = self.selected_index;
+            if !self.marked.remove(&index) {
+                self.marked.insert(index);
+            }
+        }
+        let mut delta = down - up + (page_down - page_up) * page;
+        // Up moves up on screen, which is away from the best match when it's
+        // drawn last
+        if self.reversed() {
+            delta = -delta;
+        }
+        if delta != 0 {
+            self.move_selection(delta);
+        }
+        if actions {
+            self.show_actions(self.selected_index);
+        }
+        if edit {
+            self.edit_config(ctx);
+        }
+        if forget {
+            self.forget(self.selected_index);
+        }
+        if cancel && self.picker.is_some() {
+            self.close_actions();
+        } else if cancel {
+            match core::cancel(&self.input_text, self.app_config.escape_clears_first) {
+                core::Cancel::ClearQuery => {
+                    self.input_text.clear();
+                    if !self.args.password {
+                        self.update_options();
+                    }
+                    self.selected_index = 0;
+                    self.scroll_to_selected = true;
+                }
+                core::Cancel::Exit => ctx.send_viewport_cmd(ViewportCommand::Close),
+            }
+        }
+    }
+
+    /// Opens `app.ron` in the user's editor, in a terminal, and closes the
+    /// menu
+    fn edit_config(&mut self, ctx: &Context) {
+        let Some(watcher) = &self.watcher else {
+            warn!("there's no config file to edit");
+            return;
+        };
+        let editor = launcher::user_editor(&self.app_config.editor);
+        let launched = launcher::find_terminal(&self.app_config.terminal)
+            .and_then(|terminal| launcher::edit_command(&terminal, &editor, watcher.app_path()))
+            .and_then(|command| launcher::launch(&command, false, None));
+        match launched {
+            Ok(()) => ctx.send_viewport_cmd(ViewportCommand::Close),
+            Err(e) => {
+                error!("failed to open the config in an editor: {}", e);
+                self.show_error(ctx, format!("Couldn't open the config: {}", e));
+            }
+        }
+    }
+
+    /// Shows `message` in the banner for a while, in place of any earlier one
+    fn show_error(&mut self, ctx: &Context, message: String) {
+        let until = ctx.input(|i| i.time) + BANNER_DURATION;
+        self.banner = Some((message, until));
+        ctx.request_repaint();
+    }
+
+    /// Stretches the window across the monitor once its size is known, at the
+    /// anchored edge with `layer_shell.enabled`.
+    fn size_to_screen(&mut self, ctx: &Context) {
+        if self.sized_to_screen {
+            return;
+        }
+        let Some(monitor) = ctx.input(|i| i.viewport().monitor_size) else {
+            return;
+        };
+        let height = ctx.style().spacing.interact_size.y + 2.0 * ctx.style().spacing.item_spacing.y;
+        let layer_shell = self.app_config.layer_shell;
+        let (_, y) = if layer_shell.enabled {
+            layer_shell.anchor.resolve(
+                layer_shell.margin,
+                (monitor.x, monitor.y),
+                (monitor.x, height),
+            )
+        } else {
+            self.app_config.position_mode.resolve(
+                self.app_config.position,
+                self.app_config.margin,
+                Some((monitor.x, monitor.y)),
+                (monitor.x, height),
+            )
+        };
+        ctx.send_viewport_cmd(ViewportCommand::InnerSize(vec2(monitor.x, height)));
+        ctx.send_viewport_cmd(ViewportCommand::OuterPosition(pos2(0.0, y)));
+        self.sized_to_screen = true;
+    }
+
+    /// Moves the window to its `position_mode` placement, or its anchored edge
+    /// with `layer_shell.enabled`, once the monitor and window sizes are known.
+    /// Until then it stays at the absolute `position` it was opened at.
+    fn place_window(&mut self, ctx: &Context) {
+        let layer_shell = self.app_config.layer_shell;
+        if self.placed
+            || (self.app_config.position_mode == PositionMode::Absolute && !layer_shell.enabled)
+        {
+            return;
+        }
+        let (monitor, window) = ctx.input(|i| (i.viewport().monitor_size, i.viewport().outer_rect));
+        let (Some(monitor), Some(window)) = (monitor, window) else {
+            return;
+        };
+        let monitor = (monitor.x, monitor.y);
+        let window = (window.width(), window.height());
+        let (x, y) = if layer_shell.enabled {
+            layer_shell
+                .anchor
+                .resolve(layer_shell.margin, monitor, window)
+        } else {
+            self.app_config.position_mode.resolve(
+                self.app_config.position,
+                self.app_config.margin,
+                Some(monitor),
+                window,
+            )
+        };
+        ctx.send_viewport_cmd(ViewportCommand::OuterPosition(pos2(x, y)));
+        self.placed = true;
+    }
+
+    fn show_input(&mut self, ui: &mut Ui, width: f32) {
+        // Taken before the input field sees them, which would select all on
+        // Ctrl+A
+        let edits: Vec<core::LineEdit> = ui.ctx().input_mut(|i| {
+            [
+                (Action::ClearLine, core::LineEdit::ClearLine),
+                (Action::DeleteWord, core::LineEdit::DeleteWord),
+                (Action::LineStart, core::LineEdit::Start),
+                (Action::LineEnd, core::LineEdit::End),
+            ]
+            .into_iter()
+            .filter(|(action, _)| self.keybindings.consume(i, *action) > 0)
+            .map(|(_, edit)| edit)
+            .collect()
+        });
+        let input = ui
+            .horizontal(|ui| {
+                if !self.app_config.prompt.is_empty() {
+                    ui.label(&self.app_config.prompt);
+                }
+                ui.add(
+                    TextEdit::singleline(&mut self.input_text)
+                        .password(self.args.password)
+                        .desired_width(width),
+                )
+            })
+            .inner;
+        // Selected, so the first keystroke replaces a remembered query
+        if std::mem::take(&mut self.select_query)
+            && let Some(mut state) = TextEdit::load_state(ui.ctx(), input.id)
+        {
+            let end = CCursor::new(self.input_text.chars().count());
+            let all = CCursorRange::two(CCursor::new(0), end);
+            state.cursor.set_char_range(Some(all));
+            state.store(ui.ctx(), input.id);
+        }
+        let edited = !edits.is_empty() && self.edit_input(ui.ctx(), input.id, &edits);
+        // Password input is never matched against anything
+        if (input.changed() || edited) && !self.args.password {
+            self.typed_at = Some(ui.input(|i| i.time));
+            self.update_options();
+        }
+        input.request_focus();
+        if self.loader.is_some() {
+            ui.weak("scanning…");
+        }
+    }
+
+    /// Applies readline-style `edits` to the query at the input field's
+    /// cursor. Returns whether the text changed.
+    fn edit_input(&mut self, ctx: &Context, id: egui::Id, edits: &[core::LineEdit]) -> bool {
+        let mut state = TextEdit::load_state(ctx, id).unwrap_or_default();
+        let mut cursor = state
+            .cursor
+            .char_range()
+            .map_or(self.input_text.chars().count(), |range| range.primary.index);
+        let before = self.input_text.clone();
+        for &edit in edits {
+            (self.input_text, cursor) = core::edit_line(&self.input_text, cursor, edit);
+        }
+        state
+            .cursor
+            .set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+        state.store(ctx, id);
+        ctx.request_repaint();
+        self.input_text != before
+    }
+
+    fn show_vertical(&mut self, ui: &mut Ui) {
+        if self.app_config.input_position == InputPosition::Top {
+            self.show_input(ui, f32::INFINITY);
+            let used = ui.cursor().min.y;
+            self.show_results(ui, used);
+            return;
+        }
+        ui.with_layout(egui::Layout::bottom_up(Align::Min), |ui| {
+            self.show_input(ui, f32::INFINITY);
+            // What's above the list plus the input below it, as `fit_to_rows`
+            // would see it with the input on top
+            let margin = Frame::central_panel(ui.style()).inner_margin.bottom as f32;
+            let below = ui.ctx().screen_rect().bottom() - ui.cursor().max.y - margin;
+            let used = ui.max_rect().top() + below;
+            self.show_results(ui, used);
+        });
+    }
+
+    /// Whether the results are drawn bottom-up, best match last, because the
+    /// input is below them
+    fn reversed(&self) -> bool {
+        self.app_config.layout == Layout::Vertical
+            && self.app_config.input_position == InputPosition::Bottom
+            && !self.showing_groups()
+    }
+
+    /// The vertical list of results, below or above the input depending on
+    /// `input_position`. `used` is the height taken by everything else.
+    fn show_results(&mut self, ui: &mut Ui, used: f32) {
+        if self.app_config.lines > 0 && !self.showing_groups() {
+            self.fit_to_rows(ui, used);
+        }
+        if self.args.password {
+            return;
+        }
+
+        if self.showing_groups() {
+            self.show_grouped(ui);
+            return;
+        }
+        if self.no_matches() {
+            ui.weak("No matches");
+            return;
+        }
+        if self.app_config.label_mode == LabelMode::Wrap {
+            self.show_wrapped(ui);
+            return;
+        }
+
+        let row_height = self.row_height(ui);
+        let len = self.options.len();
+        let reversed = self.reversed();
+        // Starts out scrolled to the bottom, where the best match is
+        let mut scroll = ScrollArea::vertical().stick_to_bottom(reversed);
+        if std::mem::take(&mut self.scroll_to_selected) {
+            let stride = row_height + self.app_config.row_padding;
+            let selected = core::display_row(self.selected_index, len, reversed);
+            let first = if reversed {
+                len.saturating_sub(self.first_visible + self.visible_rows)
+            } else {
+                self.first_visible
+            };
+            if selected < first {
+                scroll = scroll.vertical_scroll_offset(selected as f32 * stride);
+            } else if selected + 1 >= first + self.visible_rows {
+                let first = (selected + 2).saturating_sub(self.visible_rows);
+                scroll = scroll.vertical_scroll_offset(first as f32 * stride);
+            }
+        }
+
+        let moved = pointer_moved(ui);
+        let mut pointed = None;
+        scroll.show_rows(ui, row_height, len, |ui, rows| {
+            // The visible option nearest the input, so Alt+1 picks it
+            self.first_visible = if reversed { len - rows.end } else { rows.start };
+            self.visible_rows = rows.len();
+            style_rows(ui, row_height, self.app_config.row_padding);
+            style_labels(ui, self.app_config.label_mode);
+            ui.with_layout(egui::Layout::top_down_justified(Align::Min), |ui| {
+                for row in rows {
+                    let i = core::display_row(row, len, reversed);
+                    let selected = i == self.selected_index;
+                    let label = ui.selectable_label(selected, self.option_label(ui, i, selected));
+                    if label.clicked() || (moved && label.hovered()) {
+                        pointed = Some(i);
+                    }
+                }
+            });
+        });
+        if let Some(i) = pointed {
+            self.selected_index = i;
+        }
+    }
+
+    /// The vertical list with `LabelMode::Wrap`. Rows may take several lines,
+    /// so unlike in `show_results` every row is laid out to find where the
+    /// selected one is.
+    fn show_wrapped(&mut self, ui: &mut Ui) {
+        let scroll_to_selected = std::mem::take(&mut self.scroll_to_selected);
+        let moved = pointer_moved(ui);
+        let mut pointed = None;
+        let mut visible = Vec::new();
+        let row_height = self.row_height(ui);
+        let len = self.options.len();
+        let reversed = self.reversed();
+        ScrollArea::vertical()
+            .stick_to_bottom(reversed)
+            .show(ui, |ui| {
+                style_rows(ui, row_height, self.app_config.row_padding);
+                style_labels(ui, LabelMode::Wrap);
+                ui.with_layout(egui::Layout::top_down_justified(Align::Min), |ui| {
+                    for row in 0..len {
+                        let i = core::display_row(row, len, reversed);
+                        let selected = i == self.selected_index;
+                        let label =
+                            ui.selectable_label(selected, self.option_label(ui, i, selected));
+                        if label.clicked() || (moved && label.hovered()) {
+                            pointed = Some(i);
+                        }
+                        if selected && scroll_to_selected {
+                            label.scroll_to_me(None);
+                        }
+                        if ui.clip_rect().intersects(label.rect) {
+                            visible.push(i);
+                        }
+                    }
+                });
+            });
+        // The best match in view is the one nearest the input either way
+        self.first_visible = visible.iter().copied().min().unwrap_or(0);
+        self.visible_rows = visible.len();
+        if let Some(i) = pointed {
+            self.selected_index = i;
+        }
+    }
+
+    /// Resizes the window to show up to `lines` matches besides the `used`
+    /// height the rest takes up. Only asks when the height it needs changes,
+    /// so the window isn't resized every frame while the last request is
+    /// pending.
+    fn fit_to_rows(&mut self, ui: &Ui, used: f32) {
+        let rows = if self.args.password {
+            0
+        } else if self.no_matches() {
+            1
+        } else {
+            self.options.len().min(self.app_config.lines)
+        };
+        let list = core::list_height(rows, self.row_height(ui), self.app_config.row_padding);
+        let gap = if rows > 0 {
+            ui.spacing().item_spacing.y
+        } else {
+            0.0
+        };
+        let margin = Frame::central_panel(ui.style()).inner_margin.bottom as f32;
+        let height = (used + gap + list + margin + self.preview_height).ceil();
+
+        let Some(window) = ui.ctx().input(|i| i.viewport().inner_rect) else {
+            return;
+        };
+        if self.fitted_height == Some(height) || (window.height() - height).abs() < 1.0 {
+            return;
+        }
+        ui.ctx()
+            .send_viewport_cmd(ViewportCommand::InnerSize(vec2(window.width(), height)));
+        self.fitted_height = Some(height);
+    }
+
+    /// Height of a result row: `row_height` from the config, or the font's
+    /// height plus the usual padding. Never less than the text itself.
+    fn row_height(&self, ui: &Ui) -> f32 {
+        let text_height = ui.text_style_height(&TextStyle::Button);
+        self.app_config
+            .row_height
+            .unwrap_or(text_height + 2.0 * ui.spacing().button_padding.y)
+            .max(text_height)
+    }
+
+    /// Shows what went wrong while loading or launching, if anything, until
+    /// the banner times out.
+    fn show_banner(&mut self, ui: &mut Ui) {
+        let Some((text, until)) = &self.banner else {
+            return;
+        };
+        let remaining = until - ui.input(|i| i.time);
+        if remaining <= 0.0 {
+            self.banner = None;
+            return;
+        }
+        Frame::new()
+            .fill(ui.visuals().error_fg_color.gamma_multiply(0.25))
+            .inner_margin(4.0)
+            .corner_radius(2.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.label(text.as_str());
+            });
+        ui.ctx()
+            .request_repaint_after(Duration::from_secs_f64(remaining));
+    }
+
+    /// Whether there's nothing to list once the entries are loaded, so a
+    /// placeholder is shown instead
+    fn no_matches(&self) -> bool {
+        self.options.is_empty() && self.loader.is_none() && !self.args.password
+    }
+
+    /// Whether entries are listed under category headings. A query flattens the
+    /// groups back into a single list of matches.
+    fn showing_groups(&self) -> bool {
+        self.app_config.layout == Layout::Vertical
+            && self.app_config.group_by_category
+            && !self.groups.is_empty()
+            && self.input_text.is_empty()
+            && self.picker.is_none()
+    }
+
+    /// Lists every entry under its category heading. Only used with an empty
+    /// query, where `options` lists all entries in order.
+    fn show_grouped(&mut self, ui: &mut Ui) {
+        let scroll_to_selected = std::mem::take(&mut self.scroll_to_selected);
+        let moved = pointer_moved(ui);
+        let mut pointed = None;
+        let mut first_visible = None;
+        let mut visible_rows = 0;
+        let row_height = self.row_height(ui);
+        ScrollArea::vertical().show(ui, |ui| {
+            style_rows(ui, row_height, self.app_config.row_padding);
+            style_labels(ui, self.app_config.label_mode);
+            ui.with_layout(egui::Layout::top_down_justified(Align::Min), |ui| {
+                for item in &self.list_items {
+                    let i = match item {
+                        core::ListItem::Header(heading) => {
+                            ui.add_space(self.app_config.row_padding);
+                            ui.label(RichText::new(heading).strong());
+                            ui.separator();
+                            continue;
+                        }
+                        core::ListItem::Entry(i) => *i,
+                    };
+                    let selected = i == self.selected_index;
+                    let label = ui.selectable_label(selected, self.option_label(ui, i, selected));
+                    if label.clicked() || (moved && label.hovered()) {
+                        pointed = Some(i);
+                    }
+                    if selected && scroll_to_selected {
+                        label.scroll_to_me(None);
+                    }
+                    if ui.clip_rect().intersects(label.rect) {
+                        first_visible.get_or_insert(i);
+                        visible_rows += 1;
+                    }
+                }
+            });
+        });
+        self.first_visible = first_visible.unwrap_or(0);
+        self.visible_rows = visible_rows;
+        if let Some(i) = pointed {
+            self.selected_index = i;
+        }
+    }
+
+    fn show_horizontal(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            self.show_input(ui, ui.available_width() / 4.0);
+            if self.args.password {
+                return;
+            }
+            if self.no_matches() {
+                ui.weak("No matches");
+                return;
+            }
+
+            let scroll_to_selected = std::mem::take(&mut self.scroll_to_selected);
+            let moved = pointer_moved(ui);
+            let mut pointed = None;
+            let mut first_visible = None;
+            let mut visible_rows = 0;
+            ScrollArea::horizontal().show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for i in 0..self.options.len() {
+                        let selected = i == self.selected_index;
+                        let label =
+                            ui.selectable_label(selected, self.option_label(ui, i, selected));
+                        if label.clicked() || (moved && label.hovered()) {
+                            pointed = Some(i);
+                        }
+                        if selected && scroll_to_selected {
+                            label.scroll_to_me(None);
+                        }
+                        if ui.clip_rect().intersects(label.rect) {
+                            first_visible.get_or_insert(i);
+                            visible_rows += 1;
+                        }
+                    }
+                });
+            });
+            self.first_visible = first_visible.unwrap_or(0);
+            self.visible_rows = visible_rows;
+            if let Some(i) = pointed {
+                self.selected_index = i;
+            }
+        });
+    }
+
+    /// Handles `Alt+1`..`Alt+9`, which pick the Nth row currently in view.
+    fn handle_digit_hotkeys(&mut self, ctx: &Context) {
+        const DIGITS: [Key; 9] = [
+            Key::Num1,
+            Key::Num2,
+            Key::Num3,
+            Key::Num4,
+            Key::Num5,
+            Key::Num6,
+            Key::Num7,
+            Key::Num8,
+            Key::Num9,
+        ];
+
+        let pressed = ctx.input_mut(|i| {
+            DIGITS
+                .iter()
+                .position(|&key| i.consume_key(Modifiers::ALT, key))
+        });
+        if let Some(index) = pressed
+            .and_then(|offset| core::row_in_view(offset, self.first_visible, self.visible_rows))
+        {
+            self.activate(ctx, index);
+        }
     }
 }
 
 impl App for RMenuApp {
+    /// Fully transparent, so the panel's background alone sets the opacity
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        [0.0; 4]
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if self.app_config.remember_position
+            && let Some(position) = self.position
+            && let Some(path) = state::window_position_path()
+        {
+            state::save_position(&path, position);
+        }
+    }
+
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
-        CentralPanel::default().show(ctx, |ui| {
-            ui.visuals_mut().override_text_color = Some(egui::Color32::from_rgb(
-                (self.colors.text[0] * 255.0) as u8,
-                (self.colors.text[1] * 255.0) as u8,
-                (self.colors.text[2] * 255.0) as u8,
-            ));
-            // ui.style_mut().override_font_size = Some(self.colors.font_size);
-
-            ui.add(
-                TextEdit::singleline(&mut self.input_text)
-                    .hint_text("Type to filter...")
-                    .desired_width(f32::INFINITY),
-            );
+        // Before the input field gets a chance to see the digits
+        if self.app_config.alt_digit_hotkeys {
+            self.handle_digit_hotkeys(ctx);
+        }
+
+        self.receive_entries(ctx);
+        self.handle_navigation(ctx);
+        self.handle_copy(ctx);
+
+        if self.app_config.close_on_focus_loss {
+            self.handle_focus_loss(ctx);
+        }
+        if self.app_config.remember_position {
+            self.track_position(ctx);
+        }
+        if let Some(timeout) = self.app_config.idle_timeout_secs {
+            self.handle_idle_timeout(ctx, timeout);
+        }
+        if self.app_config.auto_accept_single {
+            self.handle_auto_accept(ctx);
+        }
+
+        self.reload_config(ctx);
+        ctx.request_repaint_after(Duration::from_secs_f64(CONFIG_POLL_INTERVAL));
 
-            if ui.button("Search").clicked() {
-                self.update_options();
+        if self.app_config.layout == Layout::Horizontal {
+            self.size_to_screen(ctx);
+        } else {
+            self.place_window(ctx);
+        }
+
+        let background = to_color32(self.colors.background).gamma_multiply(self.colors.opacity);
+        let panel = Frame::central_panel(&ctx.style()).fill(background);
+        // Before the central panel, which takes whatever space is left
+        self.show_command_preview(ctx, panel);
+        CentralPanel::default().frame(panel).show(ctx, |ui| {
+            ui.visuals_mut().override_text_color = Some(to_color32(self.colors.text));
+            ui.visuals_mut().selection.bg_fill = to_color32(self.colors.highlight);
+
+            self.show_banner(ui);
+            match self.app_config.layout {
+                Layout::Vertical => self.show_vertical(ui),
+                Layout::Horizontal => self.show_horizontal(ui),
             }
+        });
 
-            for (i, option) in self.options.iter().enumerate() {
-                let label = if i == self.selected_index {
-                    format!("> {}", option)
-                } else {
-                    option.clone()
-                };
-                if ui.button(label).clicked() {
-                    self.selected_index = i;
-                }
+        // After the input field, so text typed in the same frame is matched first
+        if ctx.input_mut(|i| self.keybindings.consume(i, Action::Accept)) > 0 {
+            self.activate(ctx, self.selected_index);
+        }
+    }
+}
+
+fn to_color32(rgb: [f32; 3]) -> Color32 {
+    Color32::from_rgb(
+        (rgb[0] * 255.0) as u8,
+        (rgb[1] * 255.0) as u8,
+        (rgb[2] * 255.0) as u8,
+    )
+}
+
+/// Whether the pointer moved since the last frame. Only then does hovering a
+/// row select it, so rows scrolling under a resting pointer, as when the
+/// selection is moved by keyboard, don't take the selection back.
+fn pointer_moved(ui: &Ui) -> bool {
+    ui.input(|i| i.pointer.delta() != Vec2::ZERO)
+}
+
+/// Lays out labels too wide for the list as `mode` says. egui would wrap
+/// them otherwise.
+fn style_labels(ui: &mut Ui, mode: LabelMode) {
+    ui.style_mut().wrap_mode = Some(match mode {
+        LabelMode::Full => TextWrapMode::Extend,
+        LabelMode::Truncate => TextWrapMode::Truncate,
+        LabelMode::Wrap => TextWrapMode::Wrap,
+    });
+}
+
+/// Makes the selectable labels in `ui` exactly `row_height` tall and
+/// `row_padding` apart, so rows line up with what `show_rows` expects.
+/// `row_height` must fit the text, see `RMenuApp::row_height`.
+fn style_rows(ui: &mut Ui, row_height: f32, row_padding: f32) {
+    let text_height = ui.text_style_height(&TextStyle::Button);
+    let spacing = ui.spacing_mut();
+    spacing.item_spacing.y = row_padding;
+    spacing.interact_size.y = row_height;
+    spacing.button_padding.y = (row_height - text_height) / 2.0;
+}
+
+/// The font embedded in the binary with the `bundled-font` feature, used
+/// unless `font_name` picks another
+const EMBEDDED_FONT: &str = "Ubuntu-M";
+
+#[cfg(feature = "bundled-font")]
+const EMBEDDED_FONT_DATA: Option<&[u8]> = Some(include_bytes!("../assets/Ubuntu-M.ttf"));
+#[cfg(not(feature = "bundled-font"))]
+const EMBEDDED_FONT_DATA: Option<&[u8]> = None;
+
+/// Builds the fonts with `font_name` first in line for proportional text.
+/// `font_name` is either a font egui knows by name, like the embedded
+/// `Ubuntu-M`, or the path of a `.ttf`/`.otf` file. Anything that can't be
+/// loaded falls back to the embedded font with a warning, or to egui's own
+/// font without the `bundled-font` feature.
+fn font_definitions(font_name: &str) -> FontDefinitions {
+    let mut fonts = FontDefinitions::default();
+    let embedded = EMBEDDED_FONT_DATA.filter(|data| match check_font(data) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("the embedded font is broken: {}", e);
+            false
+        }
+    });
+    if let Some(data) = embedded {
+        fonts.font_data.insert(
+            EMBEDDED_FONT.to_string(),
+            Arc::new(FontData::from_static(data)),
+        );
+    }
+    let fallback = embedded.map(|_| EMBEDDED_FONT);
+    let fallback_name = fallback.unwrap_or("egui's default font");
+
+    let first = if is_font_path(font_name) {
+        // egui panics on font data it can't parse, so check it up front
+        let bytes = std::fs::read(font_name)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| check_font(&bytes).map(|()| bytes));
+        match bytes {
+            Ok(bytes) => {
+                fonts
+                    .font_data
+                    .insert(font_name.to_string(), Arc::new(FontData::from_owned(bytes)));
+                Some(font_name)
             }
+            Err(e) => {
+                warn!(
+                    "couldn't load font {}: {}, using {}",
+                    font_name, e, fallback_name
+                );
+                None
+            }
+        }
+    } else if fonts.font_data.contains_key(font_name) {
+        Some(font_name)
+    } else {
+        // Without the `bundled-font` feature the default `font_name` is
+        // expected to be missing
+        if font_name != EMBEDDED_FONT || EMBEDDED_FONT_DATA.is_some() {
+            warn!("unknown font `{}`, using {}", font_name, fallback_name);
+        }
+        None
+    };
+
+    match first.or(fallback) {
+        Some(first) => {
+            debug!("using font {}", first);
+            fonts
+                .families
+                .entry(FontFamily::Proportional)
+                .or_default()
+                .insert(0, first.to_string());
+        }
+        None => debug!("using {}", fallback_name),
+    }
+    fonts
+}
+
+fn check_font(bytes: &[u8]) -> Result<(), String> {
+    ab_glyph::FontRef::try_from_slice(bytes)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn is_font_path(font_name: &str) -> bool {
+    let lower = font_name.to_ascii_lowercase();
+    font_name.contains('/') || lower.ends_with(".ttf") || lower.ends_with(".otf")
+}
+
+fn apply_font_size(ctx: &Context, size: f32) {
+    ctx.style_mut(|style| {
+        for style_kind in [TextStyle::Body, TextStyle::Button, TextStyle::Monospace] {
+            let family = if style_kind == TextStyle::Monospace {
+                FontFamily::Monospace
+            } else {
+                FontFamily::Proportional
+            };
+            style
+                .text_styles
+                .insert(style_kind, FontId::new(size, family));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_proportional(fonts: &FontDefinitions) -> &str {
+        &fonts.families[&FontFamily::Proportional][0]
+    }
+
+    /// The first proportional font when `font_name` can't be used
+    fn fallback_font() -> String {
+        let fonts = match EMBEDDED_FONT_DATA {
+            Some(_) => return EMBEDDED_FONT.to_string(),
+            None => FontDefinitions::default(),
+        };
+        first_proportional(&fonts).to_string()
+    }
+
+    #[test]
+    fn rows_span_the_width_at_the_configured_height() {
+        let ctx = Context::default();
+        let mut rects = Vec::new();
+        let mut width = 0.0;
+        let _ = ctx.run(Default::default(), |ctx| {
+            CentralPanel::default().show(ctx, |ui| {
+                style_rows(ui, 40.0, 6.0);
+                width = ui.available_width();
+                ui.with_layout(egui::Layout::top_down_justified(Align::Min), |ui| {
+                    for selected in [true, false] {
+                        rects.push(ui.selectable_label(selected, "row").rect);
+                    }
+                });
+            });
         });
+        for rect in &rects {
+            assert_eq!(rect.height(), 40.0);
+            assert_eq!(rect.width(), width);
+        }
+        assert_eq!(rects[1].top() - rects[0].bottom(), 6.0);
+    }
+
+    #[test]
+    fn label_modes_keep_long_rows_in_the_width() {
+        let long = "A very long entry name with a comment appended to it ".repeat(8);
+        let mut sizes = Vec::new();
+        for mode in [LabelMode::Full, LabelMode::Truncate, LabelMode::Wrap] {
+            let ctx = Context::default();
+            let mut size = (0.0, 0.0, 0.0);
+            let input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    pos2(0.0, 0.0),
+                    vec2(300.0, 400.0),
+                )),
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                CentralPanel::default().show(ctx, |ui| {
+                    style_rows(ui, 20.0, 0.0);
+                    style_labels(ui, mode);
+                    let width = ui.available_width();
+                    ui.with_layout(egui::Layout::top_down_justified(Align::Min), |ui| {
+                        let rect = ui.selectable_label(false, long.as_str()).rect;
+                        size = (rect.width(), rect.height(), width);
+                    });
+                });
+            });
+            sizes.push(size);
+        }
+        let [full, truncate, wrap] = sizes[..] else {
+            unreachable!()
+        };
+        assert!(full.0 > full.2);
+        assert_eq!(full.1, 20.0);
+        assert_eq!(truncate.0, truncate.2);
+        assert_eq!(truncate.1, 20.0);
+        assert_eq!(wrap.0, wrap.2);
+        assert!(wrap.1 > 20.0);
+    }
+
+    #[test]
+    fn font_name_picks_a_known_font_or_a_file() {
+        let fonts = font_definitions("Ubuntu-M");
+        assert_eq!(first_proportional(&fonts), fallback_font());
+
+        // One of egui's own fonts
+        let fonts = font_definitions("Hack");
+        assert_eq!(first_proportional(&fonts), "Hack");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Custom.ttf");
+        let hack = &FontDefinitions::default().font_data["Hack"];
+        std::fs::write(&path, &hack.font).unwrap();
+        let path = path.to_str().unwrap();
+        let fonts = font_definitions(path);
+        assert_eq!(first_proportional(&fonts), path);
+        assert!(fonts.font_data.contains_key(path));
+    }
+
+    #[test]
+    fn unloadable_fonts_fall_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let garbage = dir.path().join("garbage.otf");
+        std::fs::write(&garbage, "not a font").unwrap();
+        for name in [
+            "/no/such/font.ttf",
+            garbage.to_str().unwrap(),
+            "No Such Font",
+        ] {
+            let fonts = font_definitions(name);
+            assert_eq!(first_proportional(&fonts), fallback_font(), "{}", name);
+        }
     }
 }
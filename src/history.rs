@@ -0,0 +1,184 @@
+//! How often and how recently each entry was picked, to rank the ones used
+//! most first.
+
+use log::warn;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often an entry was picked, and when it last was
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Use {
+    pub count: u32,
+    /// Seconds since the Unix epoch
+    pub last_used: u64,
+}
+
+/// Uses by `Command::key`, kept in a file of `count<TAB>last_used<TAB>key`
+/// lines
+#[derive(Debug, Default)]
+pub struct History {
+    /// `None` keeps the history in memory only
+    path: Option<PathBuf>,
+    uses: HashMap<String, Use>,
+}
+
+impl History {
+    /// Reads the history at `path`. A missing file is an empty history, and
+    /// lines that don't parse are skipped.
+    pub fn load(path: PathBuf) -> History {
+        let uses = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text),
+            Err(_) => HashMap::new(),
+        };
+        History {
+            path: Some(path),
+            uses,
+        }
+    }
+
+    /// How `key` was used, if it was
+    pub fn get(&self, key: &str) -> Option<Use> {
+        self.uses.get(key).copied()
+    }
+
+    /// Counts a use of `key` at `now`, in seconds since the Unix epoch
+    pub fn record(&mut self, key: &str, now: u64) {
+        let entry = self.uses.entry(key.to_string()).or_insert(Use {
+            count: 0,
+            last_used: now,
+        });
+        entry.count = entry.count.saturating_add(1);
+        entry.last_used = now;
+    }
+
+    /// Drops every use of `key`, returning whether there were any
+    pub fn forget(&mut self, key: &str) -> bool {
+        self.uses.remove(key).is_some()
+    }
+
+    /// How strongly `key` should rank at `now`, `0.0` if it was never used
+    pub fn frecency(&self, key: &str, now: u64) -> f64 {
+        self.get(key).map_or(0.0, |u| frecency(u, now))
+    }
+
+    /// Writes the history back to the file it was loaded from, if any
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut keys: Vec<&String> = self.uses.keys().collect();
+        keys.sort();
+        let mut text = String::new();
+        for key in keys {
+            let u = self.uses[key];
+            text.push_str(&format!("{}\t{}\t{}\n", u.count, u.last_used, key));
+        }
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, text));
+        if let Err(e) = saved {
+            warn!("couldn't save the history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn parse(text: &str) -> HashMap<String, Use> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next()?.parse().ok()?;
+            let last_used = fields.next()?.parse().ok()?;
+            let key = fields.next().filter(|k| !k.is_empty())?;
+            Some((key.to_string(), Use { count, last_used }))
+        })
+        .collect()
+}
+
+/// The use count, weighted down the longer ago the last use was
+pub fn frecency(u: Use, now: u64) -> f64 {
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+    let weight = match now.saturating_sub(u.last_used) {
+        age if age < HOUR => 4.0,
+        age if age < DAY => 2.0,
+        age if age < 7 * DAY => 1.0,
+        age if age < 30 * DAY => 0.5,
+        _ => 0.25,
+    };
+    u.count as f64 * weight
+}
+
+/// The current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_round_trips_and_skips_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/history");
+        let mut history = History::load(path.clone());
+        assert_eq!(history.get("firefox.desktop"), None);
+
+        history.record("firefox.desktop", 100);
+        history.record("firefox.desktop", 200);
+        history.record("key\twith tab", 150);
+        history.save();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "oops\n1\tx\n").unwrap();
+
+        let history = History::load(path);
+        assert_eq!(
+            history.get("firefox.desktop"),
+            Some(Use {
+                count: 2,
+                last_used: 200
+            })
+        );
+        assert_eq!(history.get("key\twith tab").unwrap().count, 1);
+        assert_eq!(history.uses.len(), 2);
+    }
+
+    #[test]
+    fn forgotten_keys_stay_forgotten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut history = History::load(path.clone());
+        history.record("firefox.desktop", 100);
+        history.record("oneoff.desktop", 100);
+        history.save();
+
+        assert!(history.forget("oneoff.desktop"));
+        assert!(!history.forget("oneoff.desktop"));
+        assert_eq!(history.frecency("oneoff.desktop", 100), 0.0);
+        history.save();
+
+        let history = History::load(path);
+        assert_eq!(history.get("oneoff.desktop"), None);
+        assert_eq!(history.get("firefox.desktop").unwrap().count, 1);
+    }
+
+    #[test]
+    fn recent_uses_weigh_more() {
+        let day = 24 * 60 * 60;
+        let now = 100 * day;
+        let recent = Use {
+            count: 2,
+            last_used: now - 60,
+        };
+        let old = Use {
+            count: 5,
+            last_used: now - 60 * day,
+        };
+        assert!(frecency(recent, now) > frecency(old, now));
+        assert_eq!(History::default().frecency("never", now), 0.0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Finds the file for a desktop entry's `Icon` name by following the icon
+//! theme spec: the user's theme, the themes it inherits from, then `hicolor`,
+//! and finally the unthemed pixmaps.
+
+use crate::scanner::data_dirs;
+use directories::BaseDirs;
+use log::debug;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Icon file types, in the order they're preferred
+const EXTENSIONS: [&str; 3] = ["png", "svg", "xpm"];
+
+/// The theme every other theme falls back to
+const FALLBACK_THEME: &str = "hicolor";
+
+/// How a theme directory's icons may be scaled, from its `Type` key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// A size directory of a theme, like `48x48/apps`
+#[derive(Debug)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    kind: DirType,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+}
+
+impl ThemeDir {
+    /// Reads the directory's section of `index.theme`. Directories for
+    /// scaled-up displays are left out, since rmenu draws at scale 1.
+    fn parse(path: &str, keys: &BTreeMap<String, String>) -> Option<ThemeDir> {
+        let number = |key: &str| keys.get(key).and_then(|v| v.trim().parse::<u32>().ok());
+        if number("Scale").is_some_and(|scale| scale != 1) {
+            return None;
+        }
+        let size = number("Size")?;
+        let kind = match keys.get("Type").map(|t| t.trim()) {
+            Some("Fixed") => DirType::Fixed,
+            Some("Scalable") => DirType::Scalable,
+            _ => DirType::Threshold,
+        };
+        Some(ThemeDir {
+            path: path.to_string(),
+            size,
+            kind,
+            min_size: number("MinSize").unwrap_or(size),
+            max_size: number("MaxSize").unwrap_or(size),
+            threshold: number("Threshold").unwrap_or(2),
+        })
+    }
+
+    fn matches_size(&self, size: u32) -> bool {
+        match self.kind {
+            DirType::Fixed => self.size == size,
+            DirType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirType::Threshold => self.size.abs_diff(size) <= self.threshold,
+        }
+    }
+
+    fn size_distance(&self, size: u32) -> u32 {
+        match self.kind {
+            DirType::Fixed => self.size.abs_diff(size),
+            DirType::Scalable if size < self.min_size => self.min_size - size,
+            DirType::Scalable => size.saturating_sub(self.max_size),
+            DirType::Threshold if size + self.threshold < self.size => {
+                self.size - self.threshold - size
+            }
+            DirType::Threshold => size.saturating_sub(self.size + self.threshold),
+        }
+    }
+}
+
+/// An installed icon theme
+#[derive(Debug)]
+struct Theme {
+    name: String,
+    dirs: Vec<ThemeDir>,
+    inherits: Vec<String>,
+}
+
+impl Theme {
+    /// Reads the first `<base>/<name>/index.theme` found
+    fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Theme> {
+        let index = base_dirs
+            .iter()
+            .map(|base| base.join(name).join("index.theme"))
+            .find(|index| index.is_file())?;
+        let contents = fs::read_to_string(&index).ok()?;
+        let sections: BTreeMap<String, BTreeMap<String, String>> =
+            match serde_ini::from_str(&contents) {
+                Ok(sections) => sections,
+                Err(e) => {
+                    debug!("skipping icon theme {}: {}", index.display(), e);
+                    return None;
+                }
+            };
+        let theme = sections.get("Icon Theme")?;
+        let list = |key: &str| -> Vec<String> {
+            theme
+                .get(key)
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let mut dir_names = list("Directories");
+        dir_names.extend(list("ScaledDirectories"));
+        let dirs = dir_names
+            .iter()
+            .filter_map(|dir| ThemeDir::parse(dir, sections.get(dir)?))
+            .collect();
+        Some(Theme {
+            name: name.to_string(),
+            dirs,
+            inherits: list("Inherits"),
+        })
+    }
+}
+
+/// Resolves icon names to files, remembering every answer
+#[derive(Debug)]
+pub struct IconResolver {
+    /// Where themes are installed, most important first
+    base_dirs: Vec<PathBuf>,
+    /// Where unthemed icons are, searched after every theme
+    pixmap_dirs: Vec<PathBuf>,
+    /// The user's theme, then the themes it inherits from, depth first, then
+    /// `hicolor`
+    themes: Vec<Theme>,
+    cache: HashMap<(String, u32), Option<PathBuf>>,
+}
+
+impl IconResolver {
+    /// A resolver for `theme` in the standard icon directories:
+    /// `~/.icons`, then `icons` in each XDG data directory, with
+    /// `/usr/share/pixmaps` for unthemed icons.
+    pub fn new(theme: &str) -> IconResolver {
+        let mut base_dirs = Vec::new();
+        if let Some(base) = BaseDirs::new() {
+            base_dirs.push(base.home_dir().join(".icons"));
+        }
+        base_dirs.extend(data_dirs().into_iter().map(|d| d.join("icons")));
+        IconResolver::with_dirs(theme, base_dirs, vec![PathBuf::from("/usr/share/pixmaps")])
+    }
+
+    /// A resolver for `theme` installed in `base_dirs`, falling back to the
+    /// icons directly in `pixmap_dirs`
+    pub fn with_dirs(theme: &str, base_dirs: Vec<PathBuf>, pixmap_dirs: Vec<PathBuf>) -> Self {
+        let mut themes = Vec::new();
+        let mut seen = HashSet::new();
+        collect_themes(theme, &base_dirs, &mut seen, &mut themes);
+        collect_themes(FALLBACK_THEME, &base_dirs, &mut seen, &mut themes);
+        IconResolver {
+            base_dirs,
+            pixmap_dirs,
+            themes,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The file to draw `icon` from at `size` pixels. An absolute path is
+    /// used as is if it exists. A name is looked up in each theme in turn,
+    /// taking an exact size match and otherwise the closest size the theme
+    /// has, before trying the next theme.
+    pub fn lookup(&mut self, icon: &str, size: u32) -> Option<PathBuf> {
+        let key = (icon.to_string(), size);
+        if let Some(found) = self.cache.get(&key) {
+            return found.clone();
+        }
+        let found = self.find(icon, size);
+        self.cache.insert(key, found.clone());
+        found
+    }
+
+    fn find(&self, icon: &str, size: u32) -> Option<PathBuf> {
+        let path = Path::new(icon);
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        // Some entries name the file rather than the icon
+        let name = EXTENSIONS
+            .iter()
+            .find_map(|ext| icon.strip_suffix(&format!(".{}", ext)))
+            .unwrap_or(icon);
+
+        for theme in &self.themes {
+            if let Some(found) = self.find_in_theme(theme, name, size) {
+                return Some(found);
+            }
+        }
+        self.base_dirs
+            .iter()
+            .chain(&self.pixmap_dirs)
+            .find_map(|dir| find_file(dir, name))
+    }
+
+    fn find_in_theme(&self, theme: &Theme, name: &str, size: u32) -> Option<PathBuf> {
+        let in_dir = |dir: &ThemeDir| {
+            self.base_dirs
+                .iter()
+                .find_map(|base| find_file(&base.join(&theme.name).join(&dir.path), name))
+        };
+        let mut exact = theme.dirs.iter().filter(|dir| dir.matches_size(size));
+        if let Some(found) = exact.find_map(in_dir) {
+            return Some(found);
+        }
+        let mut closest: Option<(u32, PathBuf)> = None;
+        for dir in &theme.dirs {
+            let distance = dir.size_distance(size);
+            if closest.as_ref().is_some_and(|(best, _)| *best <= distance) {
+                continue;
+            }
+            if let Some(found) = in_dir(dir) {
+                closest = Some((distance, found));
+            }
+        }
+        closest.map(|(_, found)| found)
+    }
+}
+
+/// Adds `name` and the themes it inherits from, depth first, skipping
+/// themes already added or not installed
+fn collect_themes(
+    name: &str,
+    base_dirs: &[PathBuf],
+    seen: &mut HashSet<String>,
+    themes: &mut Vec<Theme>,
+) {
+    if !seen.insert(name.to_string()) {
+        return;
+    }
+    let Some(theme) = Theme::load(name, base_dirs) else {
+        debug!("icon theme {} isn't installed", name);
+        return;
+    };
+    let inherits = theme.inherits.clone();
+    themes.push(theme);
+    for parent in &inherits {
+        collect_themes(parent, base_dirs, seen, themes);
+    }
+}
+
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.is_file())
+}
+
+/// The icon theme the desktop is set to use, from the GTK or KDE settings,
+/// or `hicolor` if neither names one
+pub fn user_theme() -> String {
+    let Some(base) = BaseDirs::new() else {
+        return FALLBACK_THEME.to_string();
+    };
+    let config = base.config_dir();
+    let settings = [
+        (
+            config.join("gtk-3.0/settings.ini"),
+            "Settings",
+            "gtk-icon-theme-name",
+        ),
+        (
+            config.join("gtk-4.0/settings.ini"),
+            "Settings",
+            "gtk-icon-theme-name",
+        ),
+        (config.join("kdeglobals"), "Icons", "Theme"),
+    ];
+    settings
+        .iter()
+        .find_map(|(path, section, key)| {
+            let contents = fs::read_to_string(path).ok()?;
+            let sections: BTreeMap<String, BTreeMap<String, String>> =
+                serde_ini::from_str(&contents).ok()?;
+            let theme = sections.get(*section)?.get(*key)?.trim();
+            (!theme.is_empty()).then(|| theme.to_string())
+        })
+        .unwrap_or_else(|| FALLBACK_THEME.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn follows_the_inheritance_chain_to_hicolor() {
+        let root = tempfile::tempdir().unwrap();
+        let icons = root.path().join("icons");
+        let pixmaps = root.path().join("pixmaps");
+        write(
+            &icons.join("Child/index.theme"),
+            "[Icon Theme]\nName=Child\nInherits=Parent\nDirectories=24x24/apps\n\n\
+             [24x24/apps]\nSize=24\nType=Fixed\n",
+        );
+        // Inheriting back from Child mustn't loop
+        write(
+            &icons.join("Parent/index.theme"),
+            "[Icon Theme]\nName=Parent\nInherits=Child\n\
+             Directories=32x32/apps,32x32@2/apps\n\n\
+             [32x32/apps]\nSize=32\nType=Fixed\n\n\
+             [32x32@2/apps]\nSize=32\nScale=2\nType=Fixed\n",
+        );
+        write(
+            &icons.join("hicolor/index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=48x48/apps,scalable/apps\n\n\
+             [48x48/apps]\nSize=48\nType=Fixed\n\n\
+             [scalable/apps]\nSize=128\nMinSize=8\nMaxSize=512\nType=Scalable\n",
+        );
+        write(&icons.join("Child/24x24/apps/term.png"), "");
+        write(&icons.join("Parent/32x32/apps/firefox.png"), "");
+        write(&icons.join("Parent/32x32@2/apps/files.png"), "");
+        write(&icons.join("hicolor/48x48/apps/firefox.png"), "");
+        write(&icons.join("hicolor/scalable/apps/gimp.svg"), "");
+        write(&pixmaps.join("old.xpm"), "");
+
+        let mut resolver = IconResolver::with_dirs("Child", vec![icons.clone()], vec![pixmaps]);
+        let names: Vec<&str> = resolver.themes.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Child", "Parent", "hicolor"]);
+
+        assert_eq!(
+            resolver.lookup("term", 24),
+            Some(icons.join("Child/24x24/apps/term.png"))
+        );
+        // Parent's closest size wins over hicolor's exact one
+        assert_eq!(
+            resolver.lookup("firefox", 48),
+            Some(icons.join("Parent/32x32/apps/firefox.png"))
+        );
+        assert_eq!(
+            resolver.lookup("gimp.svg", 64),
+            Some(icons.join("hicolor/scalable/apps/gimp.svg"))
+        );
+        assert_eq!(
+            resolver.lookup("old", 48),
+            Some(root.path().join("pixmaps/old.xpm"))
+        );
+        // Only in a scale 2 directory
+        assert_eq!(resolver.lookup("files", 32), None);
+        assert_eq!(resolver.lookup("missing", 48), None);
+        assert!(resolver.cache.contains_key(&("missing".to_string(), 48)));
+    }
+
+    #[test]
+    fn size_matching() {
+        let dir = |kind, size, min_size, max_size| ThemeDir {
+            path: String::new(),
+            size,
+            kind,
+            min_size,
+            max_size,
+            threshold: 2,
+        };
+        let fixed = dir(DirType::Fixed, 48, 48, 48);
+        assert!(fixed.matches_size(48));
+        assert_eq!(fixed.size_distance(32), 16);
+
+        let scalable = dir(DirType::Scalable, 128, 16, 256);
+        assert!(scalable.matches_size(100));
+        assert_eq!(scalable.size_distance(8), 8);
+        assert_eq!(scalable.size_distance(300), 44);
+
+        let threshold = dir(DirType::Threshold, 48, 48, 48);
+        assert!(threshold.matches_size(50));
+        assert!(!threshold.matches_size(51));
+        assert_eq!(threshold.size_distance(40), 6);
+        assert_eq!(threshold.size_distance(60), 10);
+    }
+}
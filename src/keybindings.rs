@@ -0,0 +1,232 @@
+use eframe::egui::{InputState, Key, KeyboardShortcut, Modifiers};
+use log::warn;
+use std::collections::BTreeMap;
+
+/// Something the user can bind keys to in `AppConfig::keybindings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    Accept,
+    Cancel,
+    PageUp,
+    PageDown,
+    /// Marks or unmarks the selected entry with `--multi`
+    ToggleMark,
+    /// Lists the selected application's desktop actions to pick from
+    ShowActions,
+    /// Closes the menu and opens `app.ron` in an editor
+    EditConfig,
+    /// Deletes the query before the cursor
+    ClearLine,
+    /// Deletes the word before the cursor
+    DeleteWord,
+    /// Moves the cursor to the start of the query
+    LineStart,
+    /// Moves the cursor to the end of the query
+    LineEnd,
+    /// Drops the selected entry from the history, so it ranks as if it was
+    /// never picked
+    ForgetEntry,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Accept,
+        Action::Cancel,
+        Action::PageUp,
+        Action::PageDown,
+        Action::ToggleMark,
+        Action::ShowActions,
+        Action::EditConfig,
+        Action::ClearLine,
+        Action::DeleteWord,
+        Action::LineStart,
+        Action::LineEnd,
+        Action::ForgetEntry,
+    ];
+
+    /// The name used for the action in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Accept => "accept",
+            Action::Cancel => "cancel",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::ToggleMark => "toggle_mark",
+            Action::ShowActions => "show_actions",
+            Action::EditConfig => "edit_config",
+            Action::ClearLine => "clear_line",
+            Action::DeleteWord => "delete_word",
+            Action::LineStart => "line_start",
+            Action::LineEnd => "line_end",
+            Action::ForgetEntry => "forget_entry",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::MoveUp => &["Up"],
+            Action::MoveDown => &["Down"],
+            Action::Accept => &["Enter"],
+            Action::Cancel => &["Escape"],
+            Action::PageUp => &["PageUp"],
+            Action::PageDown => &["PageDown"],
+            Action::ToggleMark => &["Tab"],
+            Action::ShowActions => &["Shift+Enter"],
+            Action::EditConfig => &["Ctrl+,"],
+            Action::ClearLine => &["Ctrl+U"],
+            Action::DeleteWord => &["Ctrl+W"],
+            Action::LineStart => &["Ctrl+A"],
+            Action::LineEnd => &["Ctrl+E"],
+            Action::ForgetEntry => &["Shift+Delete"],
+        }
+    }
+}
+
+/// The config file's view of the bindings: action names to key combinations
+pub fn default_config() -> BTreeMap<String, Vec<String>> {
+    Action::ALL
+        .into_iter()
+        .map(|action| {
+            let keys = action.default_keys().iter().map(|k| k.to_string());
+            (action.name().to_string(), keys.collect())
+        })
+        .collect()
+}
+
+/// Parses a key combination like `"Ctrl+J"` or `"Shift+Tab"`. Modifiers come
+/// first, the key is named as in [`Key::from_name`].
+pub fn parse_shortcut(combination: &str) -> Result<KeyboardShortcut, String> {
+    let (modifier_names, key_name) = match combination.rsplit_once('+') {
+        // "Ctrl++" binds the plus key
+        Some((rest, "")) => (rest.strip_suffix('+').unwrap_or(rest), "+"),
+        Some((modifiers, key)) => (modifiers, key),
+        None => ("", combination),
+    };
+    let mut modifiers = Modifiers::NONE;
+    for name in modifier_names.split('+').filter(|n| !n.is_empty()) {
+        modifiers |= match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::CTRL,
+            "shift" => Modifiers::SHIFT,
+            "alt" => Modifiers::ALT,
+            _ => return Err(format!("unknown modifier `{}` in `{}`", name, combination)),
+        };
+    }
+    let key = Key::from_name(key_name)
+        .ok_or_else(|| format!("unknown key `{}` in `{}`", key_name, combination))?;
+    Ok(KeyboardShortcut::new(modifiers, key))
+}
+
+/// The parsed key bindings, checked once per frame
+#[derive(Debug)]
+pub struct Keybindings {
+    bindings: Vec<(Action, KeyboardShortcut)>,
+}
+
+impl Keybindings {
+    /// Resolves `config` against the known actions, warning about unknown
+    /// action names and keys that don't parse. Actions missing from `config`
+    /// keep their default keys.
+    pub fn from_config(config: &BTreeMap<String, Vec<String>>) -> Keybindings {
+        for name in config.keys() {
+            if Action::from_name(name).is_none() {
+                warn!("ignoring keybinding for unknown action `{}`", name);
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for action in Action::ALL {
+            let combinations: Vec<&str> = match config.get(action.name()) {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => action.default_keys().to_vec(),
+            };
+            for combination in combinations {
+                match parse_shortcut(combination) {
+                    Ok(shortcut) => bindings.push((action, shortcut)),
+                    Err(e) => warn!("keybindings.{}: {}", action.name(), e),
+                }
+            }
+        }
+        // Most specific first, so Ctrl+Shift+J isn't taken for Ctrl+J
+        bindings.sort_by_key(|(_, shortcut)| std::cmp::Reverse(modifier_count(shortcut.modifiers)));
+        Keybindings { bindings }
+    }
+
+    /// Consumes the key presses bound to `action`, returning how many there were.
+    pub fn consume(&self, input: &mut InputState, action: Action) -> usize {
+        self.bindings
+            .iter()
+            .filter(|(a, _)| *a == action)
+            .map(|(_, shortcut)| {
+                input.count_and_consume_key(shortcut.modifiers, shortcut.logical_key)
+            })
+            .sum()
+    }
+}
+
+fn modifier_count(modifiers: Modifiers) -> usize {
+    [modifiers.ctrl, modifiers.shift, modifiers.alt]
+        .into_iter()
+        .filter(|&m| m)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_combinations() {
+        assert_eq!(
+            parse_shortcut("Ctrl+J"),
+            Ok(KeyboardShortcut::new(Modifiers::CTRL, Key::J))
+        );
+        assert_eq!(
+            parse_shortcut("Up"),
+            Ok(KeyboardShortcut::new(Modifiers::NONE, Key::ArrowUp))
+        );
+        assert_eq!(
+            parse_shortcut("shift+alt+Tab"),
+            Ok(KeyboardShortcut::new(
+                Modifiers::SHIFT | Modifiers::ALT,
+                Key::Tab
+            ))
+        );
+        assert_eq!(
+            parse_shortcut("Ctrl++"),
+            Ok(KeyboardShortcut::new(Modifiers::CTRL, Key::Plus))
+        );
+        assert!(parse_shortcut("Hyper+J").is_err());
+        assert!(parse_shortcut("Ctrl+Nope").is_err());
+        assert!(parse_shortcut("").is_err());
+    }
+
+    #[test]
+    fn missing_actions_keep_their_defaults() {
+        let config = BTreeMap::from([
+            ("move_down".to_string(), vec!["Ctrl+N".to_string()]),
+            ("no_such_action".to_string(), vec!["Ctrl+X".to_string()]),
+        ]);
+        let keybindings = Keybindings::from_config(&config);
+        let keys_for = |action| {
+            keybindings
+                .bindings
+                .iter()
+                .filter(|(a, _)| *a == action)
+                .map(|(_, s)| s.logical_key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys_for(Action::MoveDown), vec![Key::N]);
+        assert_eq!(keys_for(Action::MoveUp), vec![Key::ArrowUp]);
+        assert_eq!(keybindings.bindings.len(), Action::ALL.len());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::command::Command;
+use crate::config::expand_path;
+use crate::exec::{ExecParseError, find_executable, join_args, tokenize_exec};
+use log::debug;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Carries the startup notification ID to the launched application, so the
+/// window manager can show it as starting, see [`startup_env`]
+const STARTUP_ID_VAR: &str = "DESKTOP_STARTUP_ID";
+/// The Wayland counterpart of [`STARTUP_ID_VAR`]. Only the compositor can
+/// issue these, so rmenu never sets one, it only keeps its own from leaking.
+const ACTIVATION_TOKEN_VAR: &str = "XDG_ACTIVATION_TOKEN";
+/// The entry's `StartupWMClass`, for tools that match the new window to it
+const WM_CLASS_VAR: &str = "RMENU_STARTUP_WM_CLASS";
+
+/// Why a command couldn't be started
+#[derive(Debug)]
+pub enum LaunchError {
+    /// It has to run in a terminal, but none is configured or installed
+    NoTerminal,
+    /// There's nothing to run
+    EmptyExec,
+    /// The command line couldn't be split into arguments
+    Parse(ExecParseError),
+    /// The program couldn't be started, e.g. because it isn't installed
+    Spawn(io::Error),
+}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchError::NoTerminal => {
+                f.write_str("no terminal to run it in, set `terminal` in app.ron")
+            }
+            LaunchError::EmptyExec => f.write_str("the command is empty"),
+            LaunchError::Parse(e) => write!(f, "invalid command line: {}", e),
+            LaunchError::Spawn(e) => write!(f, "couldn't start it: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LaunchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LaunchError::NoTerminal | LaunchError::EmptyExec => None,
+            LaunchError::Parse(e) => Some(e),
+            LaunchError::Spawn(e) => Some(e),
+        }
+    }
+}
+
+impl From<ExecParseError> for LaunchError {
+    fn from(e: ExecParseError) -> Self {
+        LaunchError::Parse(e)
+    }
+}
+
+impl From<io::Error> for LaunchError {
+    fn from(e: io::Error) -> Self {
+        LaunchError::Spawn(e)
+    }
+}
+
+/// Starts the application behind `entry`. With the `dbus` feature,
+/// `DBusActivatable` entries are activated over D-Bus first. If their bus
+/// name can't be derived or the call fails, their command line is run instead.
+pub fn launch_entry(entry: &Command, shell: bool, terminal: &str) -> Result<(), LaunchError> {
+    #[cfg(feature = "dbus")]
+    if entry.dbus_activatable() {
+        let (id, action) = match entry.key().split_once("::") {
+            Some((id, action)) => (id, Some(action)),
+            None => (entry.key(), None),
+        };
+        if let Some(name) = crate::dbus::bus_name(id) {
+            match crate::dbus::activate(name, action) {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!("activating {} over D-Bus failed: {}", name, e),
+            }
+        }
+    }
+    let startup_id = entry.startup_notify().then(new_startup_id);
+    spawn(
+        entry_argv(entry, shell, terminal)?,
+        entry.working_dir(),
+        &startup_env(startup_id.as_deref(), entry.startup_wm_class()),
+    )
+}
+
+/// Runs `command`, detached from rmenu's stdio. With `shell` it goes through
+/// the user's shell, see [`shell_argv`], otherwise it's split into arguments
+/// and run directly, see [`split_argv`]. `working_dir` may use `~` and
+/// environment variables, see [`expand_path`].
+pub fn launch(command: &str, shell: bool, working_dir: Option<&str>) -> Result<(), LaunchError> {
+    spawn(argv(command, shell)?, working_dir, &startup_env(None, None))
+}
+
+/// The environment changes for a launch: variables to set, or to remove
+/// when `None`. With a startup ID it's passed on in `DESKTOP_STARTUP_ID`,
+/// without one neither that nor an activation token rmenu was started with
+/// is inherited, since those belong to rmenu's own startup. A `wm_class` is
+/// set in `RMENU_STARTUP_WM_CLASS`.
+pub fn startup_env(
+    startup_id: Option<&str>,
+    wm_class: Option<&str>,
+) -> Vec<(&'static str, Option<String>)> {
+    let mut env = vec![
+        (STARTUP_ID_VAR, startup_id.map(str::to_string)),
+        (ACTIVATION_TOKEN_VAR, None),
+    ];
+    if let Some(wm_class) = wm_class {
+        env.push((WM_CLASS_VAR, Some(wm_class.to_string())));
+    }
+    env
+}
+
+/// A startup ID unique to this launch. There's no X server timestamp to
+/// give it, so it has no `_TIME` part.
+fn new_startup_id() -> String {
+    static LAUNCHES: AtomicUsize = AtomicUsize::new(0);
+    let launch = LAUNCHES.fetch_add(1, Ordering::Relaxed);
+    format!("rmenu-{}-{}", process::id(), launch)
+}
+
+fn spawn(
+    argv: Vec<String>,
+    working_dir: Option<&str>,
+    env: &[(&str, Option<String>)],
+) -> Result<(), LaunchError> {
+    let mut process = process::Command::new(&argv[0]);
+    process
+        .args(&argv[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(dir) = working_dir {
+        process.current_dir(expand_path(dir));
+    }
+    for (var, value) in env {
+        match value {
+            Some(value) => process.env(var, value),
+            None => process.env_remove(var),
+        };
+    }
+    debug!("spawning {:?} in {:?}", argv, working_dir);
+    process.spawn()?;
+    Ok(())
+}
+
+/// The argv `command` is run with: through the user's shell with `shell`,
+/// otherwise split into arguments. This is what [`launch`] spawns.
+pub fn argv(command: &str, shell: bool) -> Result<Vec<String>, LaunchError> {
+    if shell {
+        Ok(shell_argv(&user_shell(), command))
+    } else {
+        split_argv(command)
+    }
+}
+
+/// The argv [`launch_entry`] spawns for `entry`, short of D-Bus activation:
+/// its [`argv`], wrapped in the terminal found for `terminal` if the entry
+/// has `Terminal=true`, see [`find_terminal`].
+pub fn entry_argv(
+    entry: &Command,
+    shell: bool,
+    terminal: &str,
+) -> Result<Vec<String>, LaunchError> {
+    let argv = argv(entry.command(), shell)?;
+    if entry.terminal() {
+        Ok(find_terminal(terminal)?.argv(&argv))
+    } else {
+        Ok(argv)
+    }
+}
+
+/// The argv that has `shell` interpret `command`.
+///
+/// The whole text is run as shell code: `;`, `|`, `$(...)`, globs and
+/// variables all take effect. That's what piped-in shell snippets need, but
+/// it means anyone who controls the text can run anything, so only use it
+/// for input you'd type into a terminal yourself.
+pub fn shell_argv(shell: &str, command: &str) -> Vec<String> {
+    vec![shell.to_string(), "-c".to_string(), command.to_string()]
+}
+
+/// Splits `command` into argv with the desktop entry `Exec` rules, see
+/// [`tokenize_exec`]. No shell is involved, so shell syntax is passed to the
+/// program as literal arguments rather than interpreted.
+pub fn split_argv(command: &str) -> Result<Vec<String>, LaunchError> {
+    let argv = tokenize_exec(command)?;
+    if argv.is_empty() {
+        return Err(LaunchError::EmptyExec);
+    }
+    Ok(argv)
+}
+
+/// Terminals looked for when none is configured, in order, ending with
+/// `xterm` as the one most likely to be installed anywhere
+const KNOWN_TERMINALS: &[&str] = &[
+    "foot",
+    "alacritty",
+    "kitty",
+    "wezterm",
+    "ghostty",
+    "gnome-terminal",
+    "konsole",
+    "xfce4-terminal",
+    "mate-terminal",
+    "lxterminal",
+    "terminator",
+    "urxvt",
+    "st",
+    "xterm",
+];
+
+/// A terminal to run a program in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminal {
+    /// The terminal's own command line
+    pub command: Vec<String>,
+    /// What goes between `command` and the program's argv, see [`exec_args`]
+    pub exec_args: &'static [&'static str],
+}
+
+impl Terminal {
+    /// The argv that runs `program` in the terminal
+    pub fn argv(&self, program: &[String]) -> Vec<String> {
+        let exec_args = self.exec_args.iter().map(|a| a.to_string());
+        self.command
+            .iter()
+            .cloned()
+            .chain(exec_args)
+            .chain(program.iter().cloned())
+            .collect()
+    }
+}
+
+/// How the terminal `program` is told what to run: the arguments that come
+/// before the program's argv. Most take `-e`, which is also assumed for
+/// unknown ones since Debian's `x-terminal-emulator` requires it.
+pub fn exec_args(program: &str) -> &'static [&'static str] {
+    let name = Path::new(program)
+        .file_name()
+        .map_or(program.into(), |n| n.to_string_lossy());
+    match &*name {
+        "kitty" | "foot" => &[],
+        "gnome-terminal" | "ptyxis" => &["--"],
+        "wezterm" => &["start", "--"],
+        // Their `-e` takes the whole command line as one argument
+        "xfce4-terminal" | "mate-terminal" | "terminator" => &["-x"],
+        _ => &["-e"],
+    }
+}
+
+/// The terminal to run programs in: `configured` if set, then `$TERMINAL`,
+/// then Debian's `x-terminal-emulator` and the first of a list of known
+/// terminals found in `$PATH`.
+pub fn find_terminal(configured: &str) -> Result<Terminal, LaunchError> {
+    let env_terminal = std::env::var("TERMINAL").ok();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    resolve_terminal(configured, env_terminal.as_deref(), &path)
+}
+
+/// Like [`find_terminal`], given `$TERMINAL` and `$PATH`. A terminal named
+/// with arguments of its own, like `"alacritty -e"`, is taken as the whole
+/// prefix, while a bare name gets its [`exec_args`]. The terminal's program
+/// may use `~` and `$VAR`, see [`expand_path`].
+pub fn resolve_terminal(
+    configured: &str,
+    env_terminal: Option<&str>,
+    path: &OsStr,
+) -> Result<Terminal, LaunchError> {
+    for given in [Some(configured), env_terminal].into_iter().flatten() {
+        if given.trim().is_empty() {
+            continue;
+        }
+        let mut command = split_argv(given)?;
+        command[0] = expand_path(&command[0]);
+        let exec_args = match &command[..] {
+            [program] => exec_args(program),
+            _ => &[],
+        };
+        return Ok(Terminal { command, exec_args });
+    }
+    let found = ["x-terminal-emulator"]
+        .iter()
+        .chain(KNOWN_TERMINALS)
+        .find(|program| find_executable(program, path).is_some());
+    match found {
+        Some(program) => Ok(Terminal {
+            command: vec![program.to_string()],
+            exec_args: exec_args(program),
+        }),
+        None => Err(LaunchError::NoTerminal),
+    }
+}
+
+/// The command line that opens `path` in `editor` inside `terminal`, for
+/// [`launch`]. `editor` may carry arguments of its own.
+pub fn edit_command(terminal: &Terminal, editor: &str, path: &Path) -> Result<String, LaunchError> {
+    let mut program = split_argv(editor)?;
+    program.push(path.display().to_string());
+    Ok(join_args(&terminal.argv(&program)))
+}
+
+/// `$EDITOR`, or `fallback` if it isn't set
+pub fn user_editor(fallback: &str) -> String {
+    std::env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// `$SHELL`, or `sh` if it isn't set
+fn user_shell() -> String {
+    std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "sh".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_argv_passes_the_command_as_one_argument() {
+        assert_eq!(
+            shell_argv("/bin/bash", "echo $HOME | wc -c"),
+            ["/bin/bash", "-c", "echo $HOME | wc -c"]
+        );
+    }
+
+    #[test]
+    fn split_argv_leaves_shell_syntax_alone() {
+        assert_eq!(
+            split_argv(r#"echo $HOME "a b" ; rm"#).unwrap(),
+            ["echo", "$HOME", "a b", ";", "rm"]
+        );
+    }
+
+    #[test]
+    fn bad_command_lines_are_told_apart() {
+        assert!(matches!(split_argv("  "), Err(LaunchError::EmptyExec)));
+        assert!(matches!(
+            split_argv(r#"echo "oops"#),
+            Err(LaunchError::Parse(ExecParseError::UnterminatedQuote))
+        ));
+        assert!(matches!(argv("", false), Err(LaunchError::EmptyExec)));
+        assert!(matches!(
+            launch("", false, None),
+            Err(LaunchError::EmptyExec)
+        ));
+        let terminal = resolve_terminal("xterm", None, OsStr::new("")).unwrap();
+        assert!(matches!(
+            edit_command(&terminal, " ", Path::new("app.ron")),
+            Err(LaunchError::EmptyExec)
+        ));
+        assert_eq!(
+            LaunchError::Parse(ExecParseError::UnterminatedQuote).to_string(),
+            "invalid command line: missing closing quote"
+        );
+    }
+
+    #[test]
+    fn edit_command_runs_the_editor_in_the_terminal() {
+        let terminal = resolve_terminal("xterm -e", None, OsStr::new("")).unwrap();
+        let command = edit_command(
+            &terminal,
+            "nvim -p",
+            Path::new("/home/me/my config/app.ron"),
+        )
+        .unwrap();
+        assert_eq!(
+            split_argv(&command).unwrap(),
+            ["xterm", "-e", "nvim", "-p", "/home/me/my config/app.ron"]
+        );
+    }
+
+    #[test]
+    fn terminals_get_their_own_exec_arguments() {
+        assert_eq!(exec_args("xterm"), ["-e"]);
+        assert_eq!(exec_args("/usr/bin/alacritty"), ["-e"]);
+        assert_eq!(exec_args("kitty"), [] as [&str; 0]);
+        assert_eq!(exec_args("foot"), [] as [&str; 0]);
+        assert_eq!(exec_args("gnome-terminal"), ["--"]);
+        assert_eq!(exec_args("wezterm"), ["start", "--"]);
+        assert_eq!(exec_args("xfce4-terminal"), ["-x"]);
+        assert_eq!(exec_args("my-own-terminal"), ["-e"]);
+
+        let terminal = Terminal {
+            command: vec!["wezterm".into()],
+            exec_args: exec_args("wezterm"),
+        };
+        assert_eq!(
+            terminal.argv(&["htop".into()]),
+            ["wezterm", "start", "--", "htop"]
+        );
+    }
+
+    #[test]
+    fn terminal_entries_run_in_the_terminal() {
+        use crate::desktop_entry::DesktopFile;
+
+        let file = DesktopFile::parse(
+            "[Desktop Entry]\nType=Application\nName=htop\nExec=htop -d 10\nTerminal=true\n",
+        )
+        .unwrap();
+        let entry =
+            Command::from_desktop_entry("htop.desktop", file.entry().unwrap(), "C").unwrap();
+        assert!(entry.terminal());
+        assert_eq!(
+            entry_argv(&entry, false, "xterm").unwrap(),
+            ["xterm", "-e", "htop", "-d", "10"]
+        );
+
+        let entry = Command::new("htop", "htop", "htop -d 10");
+        assert_eq!(
+            entry_argv(&entry, false, "xterm").unwrap(),
+            ["htop", "-d", "10"]
+        );
+    }
+
+    #[test]
+    fn terminals_are_found_in_order() {
+        use std::os::unix::fs::PermissionsExt;
+        let bin = tempfile::tempdir().unwrap();
+        let path = bin.path().as_os_str();
+        assert!(matches!(
+            resolve_terminal("", None, path),
+            Err(LaunchError::NoTerminal)
+        ));
+        for program in ["xterm", "kitty", "x-terminal-emulator"] {
+            let file = bin.path().join(program);
+            std::fs::write(&file, "").unwrap();
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let found = resolve_terminal("", None, path).unwrap();
+            assert_eq!(found.command, [program]);
+        }
+        assert_eq!(resolve_terminal("", None, path).unwrap().exec_args, ["-e"]);
+
+        let terminal = resolve_terminal("", Some("foot"), path).unwrap();
+        assert_eq!(terminal.command, ["foot"]);
+        assert_eq!(terminal.exec_args, [] as [&str; 0]);
+        let terminal = resolve_terminal("alacritty -e", Some("foot"), path).unwrap();
+        assert_eq!(terminal.command, ["alacritty", "-e"]);
+        assert_eq!(terminal.exec_args, [] as [&str; 0]);
+        let home = std::env::var("HOME").unwrap();
+        let terminal = resolve_terminal("~/bin/foot", None, path).unwrap();
+        assert_eq!(terminal.command, [format!("{}/bin/foot", home)]);
+        assert_eq!(terminal.exec_args, [] as [&str; 0]);
+        let terminal = resolve_terminal("$HOME/bin/st -e", None, path).unwrap();
+        assert_eq!(terminal.command, [format!("{}/bin/st", home), "-e".into()]);
+        assert!(matches!(
+            resolve_terminal("xterm \"", None, path),
+            Err(LaunchError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn startup_id_is_exported_only_when_asked_for() {
+        assert_eq!(
+            startup_env(Some("rmenu-1-0"), None),
+            [
+                ("DESKTOP_STARTUP_ID", Some("rmenu-1-0".to_string())),
+                ("XDG_ACTIVATION_TOKEN", None)
+            ]
+        );
+        assert_eq!(
+            startup_env(None, None),
+            [("DESKTOP_STARTUP_ID", None), ("XDG_ACTIVATION_TOKEN", None)]
+        );
+        assert_ne!(new_startup_id(), new_startup_id());
+    }
+
+    #[test]
+    fn wm_class_is_exported_when_the_entry_has_one() {
+        let env = startup_env(Some("rmenu-1-0"), Some("firefox"));
+        assert_eq!(
+            env.last(),
+            Some(&("RMENU_STARTUP_WM_CLASS", Some("firefox".to_string())))
+        );
+        assert!(
+            startup_env(None, None)
+                .iter()
+                .all(|(var, _)| *var != "RMENU_STARTUP_WM_CLASS")
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! rmenu's menu as a library. To use its matching and ranking without a
+//! window, e.g. in tests or other programs, [`core::select_from`] and
+//! [`core::run_headless`] are the supported entry points; the rest may change
+//! along with the app.
+
+pub mod cli;
+pub mod command;
+pub mod config;
+pub mod core;
+pub mod dbus;
+pub mod desktop_entry;
+pub mod exec;
+pub mod gui;
+pub mod history;
+pub mod icons;
+pub mod keybindings;
+pub mod launcher;
+pub mod matcher;
+pub mod mime;
+pub mod plugins;
+pub mod scanner;
+pub mod sources;
+pub mod state;
@@ -1,25 +1,219 @@
-mod config;
-mod gui;
-
-use config::{AppConfig, ColorsConfig, get_config_paths, load_config};
 use eframe::NativeOptions;
-use gui::RMenuApp;
+use env_logger::Env;
+use log::warn;
+use rmenu_ng::cli::{self, Args};
+use rmenu_ng::command::{Command, dedup_commands};
+use rmenu_ng::config::{
+    AppConfig, ColorsConfig, ConfigWatcher, PositionMode, get_config_paths, load_layered,
+    save_config, system_config_files,
+};
+use rmenu_ng::gui::{MenuEntries, RMenuApp};
+use rmenu_ng::mime;
+use rmenu_ng::scanner::{
+    IdFilter, group_by_category, open_with, scan_desktop_entries, to_commands,
+};
+use rmenu_ng::sources;
+use rmenu_ng::state;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 fn main() -> eframe::Result<()> {
-    let (colors_path, app_path) = get_config_paths().expect("Failed to get config paths");
+    // Warnings only unless RUST_LOG says otherwise, e.g. RUST_LOG=debug
+    env_logger::Builder::from_env(Env::default().default_filter_or("rmenu_ng=warn"))
+        .format(|buf, record| writeln!(buf, "rmenu: [{}] {}", record.level(), record.args()))
+        .init();
+
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("rmenu: {}\nTry 'rmenu --help' for more information.", e);
+        process::exit(2);
+    });
+    if args.help {
+        print!("{}", cli::HELP);
+        return Ok(());
+    }
+    if args.version {
+        println!("{}", cli::VERSION);
+        return Ok(());
+    }
 
-    let colors: ColorsConfig = load_config(&colors_path);
-    let app_config: AppConfig = load_config(&app_path);
+    let config_paths = get_config_paths(args.profile.as_deref());
+    let (mut colors, mut app_config) = match &config_paths {
+        Some((colors_path, app_path)) => {
+            let colors: ColorsConfig = load_layered(colors_path);
+            let app_config: AppConfig = load_layered(app_path);
+            // Write out the defaults on first run so every option is discoverable,
+            // unless there's a system-wide config they would override entirely
+            if !colors_path.exists() && system_config_files(colors_path).is_empty() {
+                save_config(colors_path, &colors);
+            }
+            if !app_path.exists() && system_config_files(app_path).is_empty() {
+                save_config(app_path, &app_config);
+            }
+            (colors, app_config)
+        }
+        None => {
+            warn!("couldn't find or create the config directory, using the default config");
+            (ColorsConfig::default(), AppConfig::default())
+        }
+    };
+    colors.migrate();
+    app_config.migrate();
+    // Before validating, so a saved place that's now off screen is moved back
+    if app_config.remember_position
+        && let Some(position) = state::window_position_path().and_then(|p| state::load_position(&p))
+    {
+        app_config.position = position;
+        app_config.position_mode = PositionMode::Absolute;
+    }
+    colors.validate();
+    app_config.validate();
+    args.apply(&mut app_config);
 
+    let password = args.password;
+    let stdin = args.stdin;
+    let index = args.index;
+    let fields = args
+        .with_fields
+        .then(|| args.delimiter.clone().unwrap_or_else(|| "\t".to_string()));
+    let open = args.open.clone();
+    let group = app_config.group_by_category;
+    let include_actions = app_config.include_actions;
+    let only_show_in_when_unknown = app_config.show_only_show_in_when_unknown;
+    let ids = IdFilter {
+        hide: app_config.hide_ids.clone(),
+        only: app_config.only_ids.clone(),
+    };
+    let extra_sources = app_config.sources.clone();
+    let load = move || {
+        let mut groups = Vec::new();
+        let mut errors = Vec::new();
+        let mut commands: Vec<Command> = if password {
+            // Nothing is listed, so there's nothing to load
+            Vec::new()
+        } else if stdin && index {
+            // Keyed on the line's position, which is what gets printed. Not
+            // deduplicated, since every copy of a line has its own position.
+            let lines = io::stdin().lock().lines().map_while(Result::ok);
+            lines
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty())
+                .map(|(i, line)| Command::builder().key(i.to_string()).display(line).build())
+                .collect()
+        } else if stdin {
+            let lines = io::stdin().lock().lines().map_while(Result::ok);
+            let lines = lines.filter(|l| !l.is_empty());
+            dedup_commands(match &fields {
+                Some(delimiter) => lines.map(|l| Command::from_fields(&l, delimiter)).collect(),
+                None => lines.map(Command::from).collect(),
+            })
+        } else if let Some(file) = &open {
+            // Absolute, so it still resolves when the app runs in its own Path=
+            let file = &std::fs::canonicalize(file)
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| file.clone());
+            match mime::guess(Path::new(file)) {
+                Some(mime) => {
+                    let scan = scan_desktop_entries();
+                    let commands = dedup_commands(open_with(&scan.files, mime, file));
+                    if commands.is_empty() {
+                        errors.push(format!("No applications can open {}", mime));
+                    }
+                    commands
+                }
+                None => {
+                    errors.push(format!("Can't tell what kind of file {} is", file));
+                    Vec::new()
+                }
+            }
+        } else if group {
+            // Deduplicated per heading, which keeps the ranges valid
+            let scan = scan_desktop_entries();
+            errors = scan.errors();
+            let mut entries = Vec::new();
+            for (heading, commands) in group_by_category(
+                &scan.files,
+                include_actions,
+                only_show_in_when_unknown,
+                &ids,
+            ) {
+                let start = entries.len();
+                entries.extend(dedup_commands(commands));
+                groups.push((heading, start..entries.len()));
+            }
+            entries
+        } else {
+            let scan = scan_desktop_entries();
+            errors = scan.errors();
+            dedup_commands(to_commands(
+                &scan.files,
+                include_actions,
+                only_show_in_when_unknown,
+                &ids,
+            ))
+        };
+        // Not with --index, whose keys are input positions, or --open, which
+        // only lists applications
+        if !password && !index && open.is_none() && !extra_sources.is_empty() {
+            let extra = sources::load(&extra_sources);
+            if groups.is_empty() {
+                commands = dedup_commands(commands.into_iter().chain(extra).collect());
+            } else {
+                let start = commands.len();
+                commands.extend(dedup_commands(extra));
+                groups.push(("More".to_string(), start..commands.len()));
+            }
+        }
+        MenuEntries {
+            commands,
+            groups,
+            errors,
+        }
+    };
+
+    // Always transparent, so lowering the opacity takes effect on reload too.
+    // The background is drawn with the opacity baked in, see `RMenuApp::update`.
+    let viewport = egui::ViewportBuilder::default()
+        .with_position(egui::pos2(app_config.position.0, app_config.position.1))
+        .with_transparent(true);
+    #[cfg(feature = "wayland-layershell")]
+    let viewport =
+        if app_config.layer_shell.enabled && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            viewport
+                .with_decorations(false)
+                .with_window_level(egui::WindowLevel::AlwaysOnTop)
+        } else {
+            viewport
+        };
     let options = NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_position(egui::pos2(app_config.position.0, app_config.position.1)),
+        viewport,
         ..Default::default()
     };
 
+    let accepted = Arc::new(AtomicBool::new(false));
+    let accepted_by_app = accepted.clone();
     eframe::run_native(
         "RMenu",
         options,
-        Box::new(|cc| Ok(Box::new(RMenuApp::new(cc, colors, app_config)))),
-    )
+        Box::new(|cc| {
+            let watcher = config_paths.map(|(colors, app)| ConfigWatcher::new(colors, app));
+            Ok(Box::new(RMenuApp::new(
+                cc,
+                colors,
+                app_config,
+                args,
+                watcher,
+                load,
+                accepted_by_app,
+            )))
+        }),
+    )?;
+
+    // Like dmenu, cancelling exits with 1 so scripts can tell it apart
+    if !accepted.load(Ordering::SeqCst) {
+        process::exit(1);
+    }
+    Ok(())
 }
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+
+/// How the query is matched against entries
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The query must appear as a contiguous, case-insensitive substring
+    Substring,
+    /// The query's characters must appear in order, but not necessarily next
+    /// to each other
+    #[default]
+    Fuzzy,
+}
+
+/// A successful match of a query against a candidate string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Higher is better
+    pub score: i64,
+    /// Byte offsets of the matched characters in the candidate
+    pub indices: Vec<usize>,
+}
+
+/// Matches `query` against `candidate` with the given mode.
+pub fn match_query(candidate: &str, query: &str, mode: MatchMode) -> Option<Match> {
+    match mode {
+        MatchMode::Substring => substring_score(candidate, query),
+        MatchMode::Fuzzy => fuzzy_score(candidate, query),
+    }
+}
+
+fn chars_eq(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Whether the character at `index` starts a word: the first character, one
+/// after a separator, or an uppercase letter following a lowercase one.
+fn is_word_start(chars: &[(usize, char)], index: usize) -> bool {
+    if index == 0 {
+        return true;
+    }
+    let prev = chars[index - 1].1;
+    let current = chars[index].1;
+    matches!(prev, ' ' | '-' | '_' | '.' | '/') || (prev.is_lowercase() && current.is_uppercase())
+}
+
+/// Case-insensitive substring match, scoring earlier and word-aligned
+/// occurrences higher.
+pub fn substring_score(candidate: &str, query: &str) -> Option<Match> {
+    let chars: Vec<(usize, char)> = candidate.char_indices().collect();
+    let query: Vec<char> = query.chars().collect();
+    if query.is_empty() {
+        return Some(Match {
+            score: 0,
+            indices: Vec::new(),
+        });
+    }
+
+    let start = (0..=chars.len().checked_sub(query.len())?).find(|&start| {
+        query
+            .iter()
+            .enumerate()
+            .all(|(i, &q)| chars_eq(chars[start + i].1, q))
+    })?;
+
+    let mut score = 100 - start.min(100) as i64;
+    if is_word_start(&chars, start) {
+        score += 50;
+    }
+    Some(Match {
+        score,
+        indices: chars[start..start + query.len()]
+            .iter()
+            .map(|(i, _)| *i)
+            .collect(),
+    })
+}
+
+/// Case-insensitive subsequence match. Consecutive matches and matches at word
+/// starts score higher, gaps between matched characters lower.
+///
+/// Two alignments are tried: the earliest occurrence of each character, and
+/// one that takes word starts where it can, so initials like "vsc" line up
+/// with "Visual Studio Code". The better scoring one wins.
+pub fn fuzzy_score(candidate: &str, query: &str) -> Option<Match> {
+    let chars: Vec<(usize, char)> = candidate.char_indices().collect();
+    let earliest = fuzzy_align(&chars, query, false)?;
+    match fuzzy_align(&chars, query, true) {
+        Some(initials) if initials.score > earliest.score => Some(initials),
+        _ => Some(earliest),
+    }
+}
+
+/// Matches each character of `query` at its next occurrence in `chars`, or
+/// with `word_starts` at its next occurrence at a word start if there is one.
+fn fuzzy_align(chars: &[(usize, char)], query: &str, word_starts: bool) -> Option<Match> {
+    const MATCH: i64 = 16;
+    const CONSECUTIVE: i64 = 15;
+    const WORD_START: i64 = 30;
+    const MAX_GAP_PENALTY: i64 = 10;
+
+    let mut indices = Vec::new();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.chars() {
+        let matches = |i: &usize| chars_eq(chars[*i].1, q);
+        let at_word_start = word_starts
+            .then(|| (next..chars.len()).find(|i| matches(i) && is_word_start(chars, *i)))
+            .flatten();
+        let found = at_word_start.or_else(|| (next..chars.len()).find(matches))?;
+        score += MATCH;
+        if is_word_start(chars, found) {
+            score += WORD_START;
+        }
+        match previous {
+            Some(p) if p + 1 == found => score += CONSECUTIVE,
+            Some(p) => score -= ((found - p - 1) as i64).min(MAX_GAP_PENALTY),
+            None => score -= (found as i64).min(MAX_GAP_PENALTY),
+        }
+        indices.push(chars[found].0);
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    Some(Match { score, indices })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_reports_byte_indices() {
+        let m = substring_score("Foo Viewer", "view").unwrap();
+        assert_eq!(m.indices, vec![4, 5, 6, 7]);
+        assert!(substring_score("Foo Viewer", "fv").is_none());
+    }
+
+    #[test]
+    fn fuzzy_matches_subsequences() {
+        let m = fuzzy_score("Foo Viewer", "fv").unwrap();
+        assert_eq!(m.indices, vec![0, 4]);
+        assert!(fuzzy_score("Foo Viewer", "vf").is_none());
+    }
+
+    #[test]
+    fn fuzzy_prefers_consecutive_matches() {
+        let tight = fuzzy_score("firefox", "fire").unwrap();
+        let loose = fuzzy_score("fxixrxe", "fire").unwrap();
+        assert!(tight.score > loose.score);
+    }
+
+    #[test]
+    fn fuzzy_lines_initials_up_with_word_starts() {
+        let initials = fuzzy_score("Visual Studio Code", "vsc").unwrap();
+        assert_eq!(initials.indices, vec![0, 7, 14]);
+        for other in ["Voscope", "vsce tool"] {
+            assert!(initials.score > fuzzy_score(other, "vsc").unwrap().score);
+        }
+        let camel = fuzzy_score("GnomeSystemMonitor", "gsm").unwrap();
+        assert_eq!(camel.indices, vec![0, 5, 11]);
+    }
+
+    #[test]
+    fn indices_are_byte_offsets_for_multibyte_text() {
+        let m = fuzzy_score("Größe", "ße").unwrap();
+        assert_eq!(m.indices, vec![4, 6]);
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(fuzzy_score("anything", "").unwrap().indices.is_empty());
+        assert!(substring_score("anything", "").unwrap().indices.is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+//! Guessing a file's MIME type, for `--open`, and a URL's scheme.
+
+use std::path::Path;
+
+/// Common extensions and their MIME types. Deliberately small: it covers the
+/// files people usually open from a launcher, not every registered type.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("bmp", "image/bmp"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("pdf", "application/pdf"),
+    ("epub", "application/epub+zip"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("json", "application/json"),
+    ("js", "application/javascript"),
+    ("rs", "text/rust"),
+    ("py", "text/x-python"),
+    ("sh", "application/x-shellscript"),
+    ("c", "text/x-csrc"),
+    ("h", "text/x-chdr"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("wav", "audio/x-wav"),
+    ("mp4", "video/mp4"),
+    ("mkv", "video/x-matroska"),
+    ("webm", "video/webm"),
+    ("avi", "video/x-msvideo"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("gz", "application/gzip"),
+    ("xz", "application/x-xz"),
+    ("7z", "application/x-7z-compressed"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+];
+
+/// Guesses the MIME type of `path`: `inode/directory` for directories,
+/// otherwise by extension, ignoring case. `None` if the extension isn't known.
+pub fn guess(path: &Path) -> Option<&'static str> {
+    if path.is_dir() {
+        return Some("inode/directory");
+    }
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime)| *mime)
+}
+
+/// The scheme of `url`, like `mailto` for `mailto:a@example.org`. `None` if
+/// it doesn't start with one, as for plain paths.
+pub fn url_scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_url_schemes() {
+        assert_eq!(url_scheme("https://example.org/a:b"), Some("https"));
+        assert_eq!(url_scheme("mailto:a@example.org"), Some("mailto"));
+        assert_eq!(url_scheme("git+ssh://host/repo"), Some("git+ssh"));
+        assert_eq!(url_scheme("/tmp/a:b.txt"), None);
+        assert_eq!(url_scheme("notes.txt"), None);
+        assert_eq!(url_scheme(":nothing"), None);
+    }
+
+    #[test]
+    fn guesses_by_extension() {
+        assert_eq!(guess(Path::new("/tmp/cat.PNG")), Some("image/png"));
+        assert_eq!(guess(Path::new("notes.txt")), Some("text/plain"));
+        assert_eq!(guess(Path::new("archive.unknown")), None);
+        assert_eq!(guess(Path::new("Makefile")), None);
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(guess(dir.path()), Some("inode/directory"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Evaluates arithmetic typed into the query, like rofi's calc mode.
+
+/// Evaluates `input` as an arithmetic expression over `f64`: numbers, `+ - * /`,
+/// `^` (or `**`) for exponents, unary signs and parentheses.
+///
+/// Returns `None` for anything that isn't such an expression, so normal
+/// filtering can go ahead, and for lone numbers, which aren't worth a result.
+pub fn try_eval(input: &str) -> Option<String> {
+    let mut parser = Parser {
+        input: input.as_bytes(),
+        pos: 0,
+        operators: 0,
+    };
+    let value = parser.expr()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() || parser.operators == 0 || !value.is_finite() {
+        return None;
+    }
+    Some(format_number(value))
+}
+
+/// Whole numbers are shown without a fractional part, everything else with
+/// the shortest representation that round-trips.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// A recursive-descent parser that evaluates as it goes
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Operators and parentheses seen, to tell expressions from lone numbers
+    operators: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Consumes `token` if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.input[self.pos..].starts_with(token.as_bytes()) {
+            self.pos += token.len();
+            self.operators += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// expr = term (("+" | "-") term)*
+    fn expr(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat("+") {
+                value += self.term()?;
+            } else if self.eat("-") {
+                value -= self.term()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    /// term = unary (("*" | "/") unary)*
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        loop {
+            // `**` is an exponent, handled further down
+            if self.input[self.pos..].trim_ascii_start().starts_with(b"**") {
+                return Some(value);
+            }
+            if self.eat("*") {
+                value *= self.unary()?;
+            } else if self.eat("/") {
+                value /= self.unary()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    /// unary = ("-" | "+") unary | power
+    fn unary(&mut self) -> Option<f64> {
+        if self.eat("-") {
+            Some(-self.unary()?)
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    /// power = atom (("^" | "**") unary)?, so exponents bind right to left and
+    /// `-2^2` is `-4`
+    fn power(&mut self) -> Option<f64> {
+        let base = self.atom()?;
+        if self.eat("^") || self.eat("**") {
+            Some(base.powf(self.unary()?))
+        } else {
+            Some(base)
+        }
+    }
+
+    /// atom = number | "(" expr ")"
+    fn atom(&mut self) -> Option<f64> {
+        if self.eat("(") {
+            let value = self.expr()?;
+            return self.eat(")").then_some(value);
+        }
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || *c == b'.')
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(try_eval("1 + 2 * 3").as_deref(), Some("7"));
+        assert_eq!(try_eval("(1 + 2) * 3").as_deref(), Some("9"));
+        assert_eq!(try_eval("10 / 4").as_deref(), Some("2.5"));
+        assert_eq!(try_eval("2 ^ 3 ^ 2").as_deref(), Some("512"));
+        assert_eq!(try_eval("2**10").as_deref(), Some("1024"));
+        assert_eq!(try_eval("-2^2").as_deref(), Some("-4"));
+        assert_eq!(try_eval("3 - -1").as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn ignores_non_expressions() {
+        assert_eq!(try_eval(""), None);
+        assert_eq!(try_eval("42"), None);
+        assert_eq!(try_eval("firefox"), None);
+        assert_eq!(try_eval("1 +"), None);
+        assert_eq!(try_eval("(1 + 2"), None);
+        assert_eq!(try_eval("1 / 0"), None);
+        assert_eq!(try_eval("1.2.3 + 1"), None);
+    }
+}
@@ -0,0 +1,4 @@
+//! Sources of results that are computed from the query instead of listed up front
+pub mod calc;
+pub mod shell;
+pub mod web;
@@ -0,0 +1,19 @@
+//! Runs the query as a shell command, for the `:` prefix.
+
+use crate::command::Command;
+
+/// Starts the keys of this mode's entries, which always run through the shell
+pub const KEY_PREFIX: &str = "shell:";
+
+/// A single entry that runs `input` as typed, or none for blank input.
+pub fn run(input: &str) -> Vec<Command> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Vec::new();
+    }
+    vec![Command::new(
+        format!("{}{}", KEY_PREFIX, input),
+        format!("Run: {}", input),
+        input,
+    )]
+}
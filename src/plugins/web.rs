@@ -0,0 +1,61 @@
+//! Searches the web for the query, for the `?` prefix.
+//!
+//! The entry's command is `xdg-open <url>`, so selecting it opens the search
+//! results in the default browser. The query is percent-encoded into
+//! `SEARCH_URL`, so whatever was typed reaches the search engine as is and
+//! can't break out of the URL.
+
+use crate::command::Command;
+use crate::exec::join_args;
+
+/// Search engine URL, with `{}` standing for the encoded query
+pub const SEARCH_URL: &str = "https://duckduckgo.com/?q={}";
+
+/// A single entry that searches for `query`, or none for a blank query.
+pub fn search(query: &str) -> Vec<Command> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let url = SEARCH_URL.replace("{}", &percent_encode(query));
+    let command = join_args(&["xdg-open".to_string(), url]);
+    vec![Command::new(
+        format!("web:{}", query),
+        format!("Search the web for \"{}\"", query),
+        command,
+    )]
+}
+
+/// Encodes everything but RFC 3986 unreserved characters, with spaces as `+`
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            b' ' => out.push('+'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_query_into_the_url() {
+        assert_eq!(percent_encode("rust & egui"), "rust+%26+egui");
+        assert_eq!(percent_encode("ü?"), "%C3%BC%3F");
+
+        let results = search("  rust lang ");
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].command(),
+            "xdg-open \"https://duckduckgo.com/?q=rust+lang\""
+        );
+        assert!(search(" ").is_empty());
+    }
+}
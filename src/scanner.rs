@@ -0,0 +1,888 @@
+use crate::command::Command;
+use crate::desktop_entry::{DesktopEntry, DesktopFile, current_desktops, current_locale};
+use crate::exec::find_executable;
+use crate::mime;
+use directories::BaseDirs;
+use log::debug;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories searched for `.desktop` files, most important first:
+/// `$XDG_DATA_HOME/applications`, then each of `$XDG_DATA_DIRS`.
+pub fn application_dirs() -> Vec<PathBuf> {
+    data_dirs()
+        .into_iter()
+        .map(|d| d.join("applications"))
+        .collect()
+}
+
+/// The XDG data directories, most important first: `$XDG_DATA_HOME`, then
+/// each of `$XDG_DATA_DIRS`.
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(base) = BaseDirs::new() {
+        dirs.push(base.data_dir().to_path_buf());
+    }
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(
+        data_dirs
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from),
+    );
+    dirs
+}
+
+/// The outcome of scanning for desktop files
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub files: Vec<DesktopFile>,
+    /// Desktop files that couldn't be read or parsed
+    pub failed: Vec<ScanError>,
+}
+
+/// A desktop file that couldn't be read or parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub path: PathBuf,
+    /// The I/O or parse error
+    pub message: String,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl Scan {
+    /// Problems worth telling the user about, one message each
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.files.is_empty() {
+            errors.push("No desktop files found in the applications directories".to_string());
+        }
+        if !self.failed.is_empty() {
+            errors.push(format!(
+                "{} desktop file(s) couldn't be parsed, run with RUST_LOG=debug for details",
+                self.failed.len()
+            ));
+        }
+        errors
+    }
+}
+
+/// Strips a leading UTF-8 byte order mark and turns CRLF line endings into LF,
+/// which files saved on Windows have and the INI parser would otherwise keep
+/// in the section names and values.
+pub fn normalize(contents: &str) -> Cow<'_, str> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    if contents.contains("\r\n") {
+        Cow::Owned(contents.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(contents)
+    }
+}
+
+/// Parses every `.desktop` file in the application directories.
+pub fn scan_desktop_entries() -> Scan {
+    scan_dirs(&application_dirs())
+}
+
+/// Parses the `.desktop` files in `dirs` in parallel. When the same desktop file
+/// ID shows up in several directories, the one from the earlier directory wins.
+pub fn scan_dirs(dirs: &[PathBuf]) -> Scan {
+    scan_dirs_with(dirs, serde_ini::from_str)
+}
+
+/// Like [`scan_dirs`], but fails on the first file that can't be read or
+/// parsed, for checking a directory. Files are parsed with
+/// [`DesktopFile::parse_strict`].
+pub fn strict_scan(dirs: &[PathBuf]) -> Result<Vec<DesktopFile>, ScanError> {
+    let scan = scan_dirs_with(dirs, DesktopFile::parse_strict);
+    match scan.failed.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(scan.files),
+    }
+}
+
+fn scan_dirs_with(
+    dirs: &[PathBuf],
+    parse: fn(&str) -> Result<DesktopFile, serde_ini::de::Error>,
+) -> Scan {
+    #[cfg(feature = "scan-timing")]
+    let start = std::time::Instant::now();
+
+    let candidates = desktop_file_paths(dirs);
+    // An indexed parallel collect keeps the directory order for the dedup below
+    let parsed: Vec<Result<DesktopFile, ScanError>> = candidates
+        .par_iter()
+        .map(|(id, path)| {
+            let parsed = fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| parse(&normalize(&contents)).map_err(|e| e.to_string()));
+            match parsed {
+                Ok(mut file) => {
+                    file.id = id.clone();
+                    Ok(file)
+                }
+                Err(message) => {
+                    let error = ScanError {
+                        path: path.clone(),
+                        message,
+                    };
+                    debug!("skipping {}", error);
+                    Err(error)
+                }
+            }
+        })
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+    for parsed in parsed {
+        match parsed {
+            Ok(file) if seen.insert(file.id.clone()) => files.push(file),
+            Ok(file) => debug!("{}: shadowed by an earlier directory", file.id),
+            Err(error) => failed.push(error),
+        }
+    }
+
+    #[cfg(feature = "scan-timing")]
+    eprintln!(
+        "rmenu: parsed {} of {} desktop files in {:?}",
+        files.len(),
+        candidates.len(),
+        start.elapsed()
+    );
+    Scan { files, failed }
+}
+
+/// Lists the desktop file IDs and paths of the `.desktop` files in `dirs`
+/// and their subdirectories, in directory order.
+fn desktop_file_paths(dirs: &[PathBuf]) -> Vec<(String, PathBuf)> {
+    let mut paths = Vec::new();
+    for dir in dirs {
+        collect_desktop_files(dir, dir, &mut paths);
+    }
+    paths
+}
+
+fn collect_desktop_files(base: &Path, dir: &Path, paths: &mut Vec<(String, PathBuf)>) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        // Symlinked directories aren't followed, so a link loop can't hang the scan
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            collect_desktop_files(base, &path, paths);
+        } else if path.extension().is_some_and(|ext| ext == "desktop") {
+            paths.push((desktop_file_id(base, &path), path));
+        }
+    }
+}
+
+/// The desktop file ID of `file` found under the applications directory
+/// `base`: its path relative to `base` with `/` replaced by `-`, so
+/// `applications/kde/foo.desktop` is `kde-foo.desktop`.
+pub fn desktop_file_id(base: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(base).unwrap_or(file);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Desktop file IDs to keep out of the menu, see `AppConfig::hide_ids` and
+/// `AppConfig::only_ids`. Patterns may use `*` for any run of characters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IdFilter {
+    pub hide: Vec<String>,
+    /// Unless empty, only IDs matching one of these are listed
+    pub only: Vec<String>,
+}
+
+impl IdFilter {
+    /// Whether the desktop file `id` is listed. An ID matching both a `hide`
+    /// and an `only` pattern is hidden.
+    pub fn allows(&self, id: &str) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| glob_match(p, id));
+        !matches(&self.hide) && (self.only.is_empty() || matches(&self.only))
+    }
+}
+
+/// Whether `text` matches `pattern`, in which `*` stands for any run of
+/// characters, including none. Everything else matches only itself.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it's matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` take one more character and try again
+            star = Some((star_p, star_t + 1));
+            p = star_p + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Turns the visible application entries into menu commands, sorted by name.
+/// With `include_actions`, each entry's desktop actions are listed too.
+/// `only_show_in_when_unknown` is passed on to `DesktopEntry::should_show`.
+/// Files whose ID `ids` doesn't allow are left out.
+pub fn to_commands(
+    files: &[DesktopFile],
+    include_actions: bool,
+    only_show_in_when_unknown: bool,
+    ids: &IdFilter,
+) -> Vec<Command> {
+    let filter = EntryFilter::from_env(include_actions, only_show_in_when_unknown, ids);
+    to_commands_with(files, &filter)
+}
+
+fn to_commands_with(files: &[DesktopFile], filter: &EntryFilter) -> Vec<Command> {
+    let mut commands: Vec<Command> = files
+        .iter()
+        .filter_map(|file| filter.commands(file))
+        .flatten()
+        .collect();
+    commands.sort_by_key(|c| c.display().to_lowercase());
+    commands
+}
+
+/// The applications that declare they can open `mime` in their `MimeType`,
+/// sorted by name.
+pub fn apps_for_mime(files: &[DesktopFile], mime: &str) -> Vec<Command> {
+    apps_opening(files, |entry| entry.handles_mime(mime), &[])
+}
+
+/// Like [`apps_for_mime`], but each command opens `file`.
+pub fn open_with(files: &[DesktopFile], mime: &str, file: &str) -> Vec<Command> {
+    apps_opening(files, |entry| entry.handles_mime(mime), &[file])
+}
+
+/// The applications that declare they can open URLs with `scheme`, like
+/// `mailto`, sorted by name. See [`DesktopEntry::handles_scheme`].
+pub fn apps_for_scheme(files: &[DesktopFile], scheme: &str) -> Vec<Command> {
+    apps_opening(files, |entry| entry.handles_scheme(scheme), &[])
+}
+
+/// Like [`apps_for_scheme`] for the scheme of `url`, but each command opens
+/// `url`. Empty if `url` has no scheme.
+pub fn open_url_with(files: &[DesktopFile], url: &str) -> Vec<Command> {
+    match mime::url_scheme(url) {
+        Some(scheme) => apps_opening(files, |entry| entry.handles_scheme(scheme), &[url]),
+        None => Vec::new(),
+    }
+}
+
+fn apps_opening(
+    files: &[DesktopFile],
+    handles: impl Fn(&DesktopEntry) -> bool,
+    opening: &[&str],
+) -> Vec<Command> {
+    let locale = current_locale();
+    let mut commands: Vec<Command> = files
+        .iter()
+        .filter_map(|file| {
+            let entry = file.entry()?;
+            // NoDisplay entries are often just there to handle files, so only
+            // Hidden ones are left out
+            if entry.hidden == Some(true) || !handles(entry) {
+                return None;
+            }
+            Command::from_desktop_entry_opening(&file.id, entry, &locale, opening)
+        })
+        .collect();
+    commands.sort_by_key(|c| c.display().to_lowercase());
+    commands
+}
+
+/// The parts of the environment that decide which entries make it into the menu
+struct EntryFilter {
+    desktops: Vec<String>,
+    locale: String,
+    path: OsString,
+    include_actions: bool,
+    only_show_in_when_unknown: bool,
+    ids: IdFilter,
+}
+
+impl EntryFilter {
+    fn from_env(include_actions: bool, only_show_in_when_unknown: bool, ids: &IdFilter) -> Self {
+        Self {
+            desktops: current_desktops(),
+            locale: current_locale(),
+            path: env::var_os("PATH").unwrap_or_default(),
+            include_actions,
+            only_show_in_when_unknown,
+            ids: ids.clone(),
+        }
+    }
+
+    /// The menu commands for `file`: the application itself, carrying its
+    /// actions, followed by the actions as rows of their own if enabled.
+    /// `None` if it has no visible application entry or its `TryExec` program
+    /// isn't installed.
+    fn commands(&self, file: &DesktopFile) -> Option<Vec<Command>> {
+        if !self.ids.allows(&file.id) {
+            debug!("{}: left out by hide_ids or only_ids", file.id);
+            return None;
+        }
+        let Some(entry) = file.entry() else {
+            debug!("{}: no [Desktop Entry] section", file.id);
+            return None;
+        };
+        if let Some(key) = entry.hidden_by(&self.desktops, self.only_show_in_when_unknown) {
+            debug!("{}: hidden by {}", file.id, key);
+            return None;
+        }
+        if let Some(try_exec) = &entry.try_exec
+            && find_executable(try_exec, &self.path).is_none()
+        {
+            debug!("{}: TryExec program {} isn't installed", file.id, try_exec);
+            return None;
+        }
+        let Some(command) = Command::from_desktop_entry(&file.id, entry, &self.locale) else {
+            debug!(
+                "{}: not a launchable application (Type={}, no Name or a bad Exec)",
+                file.id, entry.entry_type
+            );
+            return None;
+        };
+        let action_ids = entry.actions.as_ref().map_or(&[][..], |a| &a.0[..]);
+        let actions: Vec<Command> = action_ids
+            .iter()
+            .filter_map(|action_id| {
+                let action = file.action(action_id)?;
+                Command::from_desktop_action(&file.id, entry, action_id, action, &self.locale)
+            })
+            .collect();
+        // Picked from the application's row either way, listed only if asked
+        let mut commands = vec![command.with_actions(actions.clone())];
+        if self.include_actions {
+            commands.extend(actions);
+        }
+        Some(commands)
+    }
+}
+
+/// Buckets the visible application entries under a heading for their primary
+/// main category, e.g. "Development" or "Internet". Headings are sorted, with
+/// entries that have no recognizable category collected under "Other" last.
+/// Desktop actions, when included, are filed under their application's heading.
+pub fn group_by_category(
+    files: &[DesktopFile],
+    include_actions: bool,
+    only_show_in_when_unknown: bool,
+    ids: &IdFilter,
+) -> Vec<(String, Vec<Command>)> {
+    let filter = EntryFilter::from_env(include_actions, only_show_in_when_unknown, ids);
+    let mut groups: Vec<(String, Vec<Command>)> = Vec::new();
+    for file in files {
+        let Some(commands) = filter.commands(file) else {
+            continue;
+        };
+        let heading = heading(commands[0].categories());
+        match groups.iter_mut().find(|(h, _)| h == heading) {
+            Some((_, group)) => group.extend(commands),
+            None => groups.push((heading.to_string(), commands)),
+        }
+    }
+
+    for (_, commands) in &mut groups {
+        commands.sort_by_key(|c| c.display().to_lowercase());
+    }
+    groups.sort_by(|(a, _), (b, _)| (a == "Other").cmp(&(b == "Other")).then(a.cmp(b)));
+    groups
+}
+
+/// The registered main categories of the freedesktop menu spec
+const MAIN_CATEGORIES: &[&str] = &[
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+/// Additional categories and the main category they're related to, for
+/// entries that don't list a main category themselves
+const RELATED_CATEGORIES: &[(&str, &str)] = &[
+    ("Building", "Development"),
+    ("Debugger", "Development"),
+    ("IDE", "Development"),
+    ("GUIDesigner", "Development"),
+    ("Profiling", "Development"),
+    ("RevisionControl", "Development"),
+    ("Translation", "Development"),
+    ("WebDevelopment", "Development"),
+    ("Calendar", "Office"),
+    ("ContactManagement", "Office"),
+    ("Database", "Office"),
+    ("Dictionary", "Office"),
+    ("Chart", "Office"),
+    ("Finance", "Office"),
+    ("Presentation", "Office"),
+    ("Spreadsheet", "Office"),
+    ("WordProcessor", "Office"),
+    ("2DGraphics", "Graphics"),
+    ("3DGraphics", "Graphics"),
+    ("Scanning", "Graphics"),
+    ("Photography", "Graphics"),
+    ("Viewer", "Graphics"),
+    ("Email", "Network"),
+    ("InstantMessaging", "Network"),
+    ("Chat", "Network"),
+    ("IRCClient", "Network"),
+    ("FileTransfer", "Network"),
+    ("News", "Network"),
+    ("P2P", "Network"),
+    ("RemoteAccess", "Network"),
+    ("WebBrowser", "Network"),
+    ("Midi", "Audio"),
+    ("Mixer", "Audio"),
+    ("Sequencer", "Audio"),
+    ("Music", "Audio"),
+    ("Player", "AudioVideo"),
+    ("Recorder", "AudioVideo"),
+    ("TV", "Video"),
+    ("ActionGame", "Game"),
+    ("ArcadeGame", "Game"),
+    ("BoardGame", "Game"),
+    ("CardGame", "Game"),
+    ("Emulator", "System"),
+    ("FileManager", "System"),
+    ("TerminalEmulator", "System"),
+    ("Monitor", "System"),
+    ("Security", "Settings"),
+    ("DesktopSettings", "Settings"),
+    ("HardwareSettings", "Settings"),
+    ("PackageManager", "Settings"),
+    ("TextEditor", "Utility"),
+    ("Archiving", "Utility"),
+    ("Compression", "Utility"),
+    ("Calculator", "Utility"),
+    ("Clock", "Utility"),
+    ("Accessibility", "Utility"),
+    ("Math", "Science"),
+    ("Astronomy", "Science"),
+    ("Biology", "Science"),
+    ("Chemistry", "Science"),
+    ("Physics", "Science"),
+];
+
+/// The first main category in `categories`, falling back to the main category
+/// related to the first additional category that has one.
+pub fn main_category(categories: &[String]) -> Option<&'static str> {
+    let main = categories
+        .iter()
+        .find_map(|c| MAIN_CATEGORIES.iter().find(|m| **m == c.as_str()));
+    if let Some(main) = main {
+        return Some(main);
+    }
+    categories.iter().find_map(|c| {
+        RELATED_CATEGORIES
+            .iter()
+            .find(|(sub, _)| *sub == c.as_str())
+            .map(|(_, main)| *main)
+    })
+}
+
+/// The heading an entry with `categories` is grouped under, "Other" if none
+/// of them is recognized
+pub fn heading(categories: &[String]) -> &'static str {
+    main_category(categories).map_or("Other", category_heading)
+}
+
+/// The heading shown for a main category
+fn category_heading(main: &str) -> &str {
+    match main {
+        "AudioVideo" | "Audio" | "Video" => "Multimedia",
+        "Game" => "Games",
+        "Network" => "Internet",
+        "Utility" => "Accessories",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories(list: &[&str]) -> Vec<String> {
+        list.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn write_entry(dir: &Path, file: &str, name: &str) {
+        let contents = format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec=foo\n",
+            name
+        );
+        fs::write(dir.join(file), contents).unwrap();
+    }
+
+    #[test]
+    fn earlier_directories_take_precedence() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        write_entry(user.path(), "foo.desktop", "User Foo");
+        write_entry(system.path(), "foo.desktop", "System Foo");
+        write_entry(system.path(), "bar.desktop", "Bar");
+        fs::write(user.path().join("notes.txt"), "not a desktop file").unwrap();
+
+        let files = scan_dirs(&[user.path().to_path_buf(), system.path().to_path_buf()]).files;
+        let mut names: Vec<_> = files
+            .iter()
+            .map(|f| f.entry().unwrap().name.get_for_locale("").unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["Bar", "User Foo"]);
+    }
+
+    #[test]
+    fn byte_order_marks_and_crlf_are_cleaned_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = "\u{feff}[Desktop Entry]\r\nType=Application\r\n\
+                        Name=Notepad\r\nExec=notepad %f\r\nCategories=Utility;\r\n";
+        fs::write(dir.path().join("notepad.desktop"), contents).unwrap();
+
+        let scan = scan_dirs(&[dir.path().to_path_buf()]);
+        assert!(scan.failed.is_empty());
+        let entry = scan.files[0].entry().unwrap();
+        assert_eq!(entry.name.get_for_locale(""), Some("Notepad"));
+        assert_eq!(entry.exec.as_deref(), Some("notepad %f"));
+        assert_eq!(entry.categories.as_ref().unwrap().0, ["Utility"]);
+        assert_eq!(normalize("a\r\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn desktop_file_ids_join_subdirectories_with_hyphens() {
+        let base = Path::new("/usr/share/applications");
+        assert_eq!(
+            desktop_file_id(base, &base.join("firefox.desktop")),
+            "firefox.desktop"
+        );
+        assert_eq!(
+            desktop_file_id(base, &base.join("kde/foo.desktop")),
+            "kde-foo.desktop"
+        );
+        assert_eq!(
+            desktop_file_id(base, &base.join("a/b-c/d.desktop")),
+            "a-b-c-d.desktop"
+        );
+    }
+
+    #[test]
+    fn nested_entries_are_scanned_with_their_ids() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        fs::create_dir_all(system.path().join("kde")).unwrap();
+        write_entry(system.path(), "kde/foo.desktop", "KDE Foo");
+        write_entry(system.path(), "foo.desktop", "Foo");
+        // Same ID as kde/foo.desktop, so it shadows it
+        write_entry(user.path(), "kde-foo.desktop", "User KDE Foo");
+
+        let files = scan_dirs(&[user.path().to_path_buf(), system.path().to_path_buf()]).files;
+        let mut found: Vec<_> = files
+            .iter()
+            .map(|f| {
+                let name = f.entry().unwrap().name.get_for_locale("").unwrap();
+                (f.id.as_str(), name)
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![("foo.desktop", "Foo"), ("kde-foo.desktop", "User KDE Foo")]
+        );
+    }
+
+    #[test]
+    fn unparsable_files_are_reported() {
+        let apps = tempfile::tempdir().unwrap();
+        write_entry(apps.path(), "good.desktop", "Good");
+        fs::write(apps.path().join("bad.desktop"), "[Desktop Entry\n").unwrap();
+
+        let scan = scan_dirs(&[apps.path().to_path_buf()]);
+        assert_eq!(scan.files.len(), 1);
+        assert_eq!(scan.failed.len(), 1);
+        assert_eq!(scan.failed[0].path, apps.path().join("bad.desktop"));
+        assert!(!scan.failed[0].message.is_empty());
+        assert_eq!(scan.errors().len(), 1);
+
+        let error = strict_scan(&[apps.path().to_path_buf()]).unwrap_err();
+        assert_eq!(error.path, apps.path().join("bad.desktop"));
+        assert!(error.to_string().contains("bad.desktop: "));
+        fs::remove_file(apps.path().join("bad.desktop")).unwrap();
+        assert_eq!(strict_scan(&[apps.path().to_path_buf()]).unwrap().len(), 1);
+
+        let empty = tempfile::tempdir().unwrap();
+        let scan = scan_dirs(&[empty.path().to_path_buf()]);
+        assert_eq!(scan.errors().len(), 1);
+    }
+
+    #[test]
+    fn apps_are_matched_by_mime_type() {
+        let apps = tempfile::tempdir().unwrap();
+        for (file, name, exec, mime) in [
+            (
+                "viewer.desktop",
+                "Viewer",
+                "viewer %f",
+                "image/png;image/jpeg;",
+            ),
+            ("editor.desktop", "Editor", "editor %U", "text/plain;"),
+            ("paint.desktop", "Paint", "paint --open %F", "image/png;"),
+        ] {
+            let contents = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec={}\nMimeType={}\nNoDisplay=true\n",
+                name, exec, mime
+            );
+            fs::write(apps.path().join(file), contents).unwrap();
+        }
+        let files = scan_dirs(&[apps.path().to_path_buf()]).files;
+
+        let names: Vec<_> = apps_for_mime(&files, "image/png")
+            .iter()
+            .map(|c| c.display().to_string())
+            .collect();
+        assert_eq!(names, ["Paint", "Viewer"]);
+        assert!(apps_for_mime(&files, "video/mp4").is_empty());
+
+        let commands: Vec<_> = open_with(&files, "image/png", "/tmp/a b.png")
+            .iter()
+            .map(|c| c.command().to_string())
+            .collect();
+        assert_eq!(
+            commands,
+            [r#"paint --open "/tmp/a b.png""#, r#"viewer "/tmp/a b.png""#]
+        );
+    }
+
+    #[test]
+    fn apps_are_matched_by_url_scheme() {
+        let apps = tempfile::tempdir().unwrap();
+        for (file, name, exec, keys) in [
+            (
+                "browser.desktop",
+                "Browser",
+                "browser %u",
+                "MimeType=text/html;x-scheme-handler/http;x-scheme-handler/https;",
+            ),
+            (
+                "konqueror.desktop",
+                "Konqueror",
+                "konqueror %U",
+                "X-KDE-Protocols=http,https,ftp",
+            ),
+            (
+                "mail.desktop",
+                "Mail",
+                "mail %u",
+                "MimeType=x-scheme-handler/mailto;",
+            ),
+        ] {
+            let contents = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec={}\n{}\n",
+                name, exec, keys
+            );
+            fs::write(apps.path().join(file), contents).unwrap();
+        }
+        let files = scan_dirs(&[apps.path().to_path_buf()]).files;
+
+        let names: Vec<_> = apps_for_scheme(&files, "https")
+            .iter()
+            .map(|c| c.display().to_string())
+            .collect();
+        assert_eq!(names, ["Browser", "Konqueror"]);
+        assert!(apps_for_scheme(&files, "tel").is_empty());
+
+        let commands: Vec<_> = open_url_with(&files, "mailto:a@example.org")
+            .iter()
+            .map(|c| c.command().to_string())
+            .collect();
+        assert_eq!(commands, ["mail mailto:a@example.org"]);
+        assert!(open_url_with(&files, "/tmp/a.html").is_empty());
+    }
+
+    #[test]
+    fn main_category_prefers_registered_main_categories() {
+        assert_eq!(
+            main_category(&categories(&["GTK", "WebBrowser", "Network"])),
+            Some("Network")
+        );
+        assert_eq!(
+            main_category(&categories(&["IDE", "Qt"])),
+            Some("Development")
+        );
+        assert_eq!(main_category(&categories(&["X-Custom"])), None);
+    }
+
+    #[test]
+    fn headings_use_friendly_names() {
+        assert_eq!(category_heading("Network"), "Internet");
+        assert_eq!(category_heading("Development"), "Development");
+    }
+
+    #[test]
+    fn missing_try_exec_hides_the_entry() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = tempfile::tempdir().unwrap();
+        let installed = bin.path().join("installed");
+        fs::write(&installed, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&installed, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(bin.path().join("not-executable"), "").unwrap();
+
+        let apps = tempfile::tempdir().unwrap();
+        let absolute = installed.display().to_string();
+        for (id, try_exec) in [
+            ("installed", "installed"),
+            ("absolute", absolute.as_str()),
+            ("missing", "missing"),
+            ("not-executable", "not-executable"),
+        ] {
+            let contents = format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=foo\nTryExec={}\n",
+                id, try_exec
+            );
+            fs::write(apps.path().join(format!("{}.desktop", id)), contents).unwrap();
+        }
+
+        let filter = EntryFilter {
+            desktops: Vec::new(),
+            locale: String::new(),
+            path: bin.path().as_os_str().to_owned(),
+            include_actions: false,
+            only_show_in_when_unknown: false,
+            ids: IdFilter::default(),
+        };
+        let files = scan_dirs(&[apps.path().to_path_buf()]).files;
+        let names: Vec<_> = to_commands_with(&files, &filter)
+            .iter()
+            .map(|c| c.display().to_string())
+            .collect();
+        assert_eq!(names, vec!["absolute", "installed"]);
+    }
+
+    #[test]
+    fn ids_are_hidden_or_allowed_by_pattern() {
+        assert!(glob_match("foo.desktop", "foo.desktop"));
+        assert!(!glob_match("foo.desktop", "foo.desktop.bak"));
+        assert!(glob_match("libreoffice-*", "libreoffice-writer.desktop"));
+        assert!(glob_match("*-installer*", "steam-installer.desktop"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("libreoffice-*", "org.libreoffice.desktop"));
+
+        let ids = IdFilter {
+            hide: vec![
+                "libreoffice-startcenter.desktop".into(),
+                "*-installer.desktop".into(),
+            ],
+            only: Vec::new(),
+        };
+        assert!(ids.allows("firefox.desktop"));
+        assert!(!ids.allows("libreoffice-startcenter.desktop"));
+        assert!(!ids.allows("steam-installer.desktop"));
+
+        // Hiding wins over allowing
+        let ids = IdFilter {
+            hide: vec!["libreoffice-startcenter.desktop".into()],
+            only: vec!["libreoffice-*".into()],
+        };
+        assert!(ids.allows("libreoffice-writer.desktop"));
+        assert!(!ids.allows("libreoffice-startcenter.desktop"));
+        assert!(!ids.allows("firefox.desktop"));
+    }
+
+    #[test]
+    fn desktop_actions_become_their_own_commands() {
+        let apps = tempfile::tempdir().unwrap();
+        fs::write(
+            apps.path().join("firefox.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Firefox\nExec=firefox %u\n\
+             Icon=firefox\nCategories=Network;WebBrowser;\n\
+             Actions=new-private-window;missing;\n\n\
+             [Desktop Action new-private-window]\nName=New Private Window\n\
+             Exec=firefox --private-window %u\n",
+        )
+        .unwrap();
+        let mut filter = EntryFilter {
+            desktops: Vec::new(),
+            locale: String::new(),
+            path: OsString::new(),
+            include_actions: true,
+            only_show_in_when_unknown: false,
+            ids: IdFilter::default(),
+        };
+        let files = scan_dirs(&[apps.path().to_path_buf()]).files;
+
+        let commands = to_commands_with(&files, &filter);
+        let listed: Vec<_> = commands
+            .iter()
+            .map(|c| (c.key(), c.display(), c.command()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("firefox.desktop", "Firefox", "firefox"),
+                (
+                    "firefox.desktop::new-private-window",
+                    "Firefox: New Private Window",
+                    "firefox --private-window"
+                ),
+            ]
+        );
+
+        for command in &commands {
+            assert_eq!(command.icon(), Some("firefox"));
+            assert_eq!(command.categories(), ["Network", "WebBrowser"]);
+        }
+
+        // The application carries its actions for the picker either way
+        assert_eq!(commands[0].actions(), &commands[1..]);
+        filter.include_actions = false;
+        let commands = to_commands_with(&files, &filter);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].actions()[0].key(),
+            "firefox.desktop::new-private-window"
+        );
+    }
+}
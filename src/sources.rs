@@ -0,0 +1,121 @@
+//! Extra menu entries produced by commands from the config, see
+//! `AppConfig::sources`.
+
+use crate::command::Command;
+use crate::launcher;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::process::{self, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a source's command may take before it's killed and its entries
+/// left out
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where extra entries come from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SourceSpec {
+    /// Runs `exec` once at startup and lists each line it prints. `exec` is
+    /// split into arguments like a desktop entry's `Exec`, so pipelines need
+    /// an explicit `sh -c "..."`.
+    Command { exec: String },
+}
+
+/// The entries of every source, in order. A source that fails contributes
+/// nothing.
+pub fn load(sources: &[SourceSpec]) -> Vec<Command> {
+    sources.iter().flat_map(run).collect()
+}
+
+fn run(source: &SourceSpec) -> Vec<Command> {
+    match source {
+        SourceSpec::Command { exec } => match run_command(exec, TIMEOUT) {
+            Ok(output) => parse_lines(&output),
+            Err(e) => {
+                warn!("source `{}` failed: {}", exec, e);
+                Vec::new()
+            }
+        },
+    }
+}
+
+/// An entry for each non-empty line of `output`, which is its key, display
+/// text and command, like lines read with `--stdin`
+pub fn parse_lines(output: &str) -> Vec<Command> {
+    output
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Command::from(line.to_string()))
+        .collect()
+}
+
+/// Runs `exec` and returns what it printed, failing if it can't be started,
+/// exits unsuccessfully or is still running after `timeout`.
+fn run_command(exec: &str, timeout: Duration) -> Result<String, String> {
+    let argv = launcher::split_argv(exec).map_err(|e| e.to_string())?;
+    let mut child = process::Command::new(&argv[0])
+        .args(&argv[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // Read on the side, so a full pipe can't keep the child from exiting
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {:?}", timeout));
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    if !status.success() {
+        return Err(status.to_string());
+    }
+    reader
+        .join()
+        .map_err(|_| "reading the output failed".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_line_becomes_an_entry() {
+        let commands = parse_lines("firefox\r\n\n  \nxterm -e htop\n");
+        let lines: Vec<&str> = commands.iter().map(Command::key).collect();
+        assert_eq!(lines, ["firefox", "xterm -e htop"]);
+        assert_eq!(commands[1].display(), "xterm -e htop");
+        assert_eq!(commands[1].command(), "xterm -e htop");
+    }
+
+    #[test]
+    fn failing_commands_give_no_entries() {
+        assert_eq!(run_command("echo a", TIMEOUT).unwrap(), "a\n");
+        assert!(run_command("false", TIMEOUT).is_err());
+        assert!(run_command("sleep 5", Duration::from_millis(50)).is_err());
+        assert!(run_command("rmenu-no-such-program", TIMEOUT).is_err());
+        assert!(
+            load(&[SourceSpec::Command {
+                exec: "false".into()
+            }])
+            .is_empty()
+        );
+    }
+}
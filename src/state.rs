@@ -0,0 +1,105 @@
+//! Small files rmenu keeps between runs, in `$XDG_STATE_HOME/rmenu`.
+
+use directories::ProjectDirs;
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the last submitted query is kept for `AppConfig::remember_query`
+pub fn last_query_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("last_query"))
+}
+
+/// Where the window's last position is kept for
+/// `AppConfig::remember_position`
+pub fn window_position_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("window_position"))
+}
+
+/// Where the launch history is kept, see `History`
+pub fn history_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("history"))
+}
+
+fn state_dir() -> Option<PathBuf> {
+    let dirs = ProjectDirs::from("com", "example", "rmenu")?;
+    // Only Linux has a state dir, the local data dir is the closest elsewhere
+    Some(
+        dirs.state_dir()
+            .unwrap_or(dirs.data_local_dir())
+            .to_path_buf(),
+    )
+}
+
+/// The query saved at `path`, or an empty one if it's missing or unreadable
+pub fn load_last_query(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+/// Saves `query` to `path`, creating its directory if needed
+pub fn save_last_query(path: &Path, query: &str) {
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, query));
+    if let Err(e) = saved {
+        warn!("couldn't save the query to {}: {}", path.display(), e);
+    }
+}
+
+/// The window position saved at `path`, if there's a valid one
+pub fn load_position(path: &Path) -> Option<(f32, f32)> {
+    let saved = fs::read_to_string(path).ok()?;
+    let (x, y) = saved.trim().split_once(' ')?;
+    let (x, y) = (x.parse::<f32>().ok()?, y.parse::<f32>().ok()?);
+    (x.is_finite() && y.is_finite()).then_some((x, y))
+}
+
+/// Saves `position` to `path` as `x y`, creating its directory if needed
+pub fn save_position(path: &Path, (x, y): (f32, f32)) {
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, format!("{} {}\n", x, y)));
+    if let Err(e) = saved {
+        warn!(
+            "couldn't save the window position to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_query_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/last_query");
+        assert_eq!(load_last_query(&path), "");
+
+        save_last_query(&path, "fire fox");
+        assert_eq!(load_last_query(&path), "fire fox");
+
+        // Not UTF-8
+        fs::write(&path, [0xff, 0xfe]).unwrap();
+        assert_eq!(load_last_query(&path), "");
+    }
+
+    #[test]
+    fn window_position_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/window_position");
+        assert_eq!(load_position(&path), None);
+
+        save_position(&path, (120.5, 40.0));
+        assert_eq!(load_position(&path), Some((120.5, 40.0)));
+
+        for bad in ["", "120", "a b", "NaN 4", "1 2 3"] {
+            fs::write(&path, bad).unwrap();
+            assert_eq!(load_position(&path), None, "{:?}", bad);
+        }
+    }
+}